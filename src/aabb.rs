@@ -0,0 +1,149 @@
+use crate::ray::Ray;
+use na::{Isometry3, Point3, Vector3};
+
+// axis-aligned bounding box
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+  pub min: Point3<f32>,
+  pub max: Point3<f32>,
+}
+
+impl Aabb {
+  pub fn new(min: Point3<f32>, max: Point3<f32>) -> Aabb {
+    Aabb { min, max }
+  }
+
+  // contains nothing, so it is the identity for `union`
+  pub fn empty() -> Aabb {
+    Aabb {
+      min: Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+      max: Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+    }
+  }
+
+  pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Point3<f32>>) -> Aabb {
+    points
+      .into_iter()
+      .fold(Aabb::empty(), |aabb, point| aabb.grow(point))
+  }
+
+  pub fn grow(&self, point: &Point3<f32>) -> Aabb {
+    Aabb {
+      min: self.min.inf(point),
+      max: self.max.sup(point),
+    }
+  }
+
+  pub fn union(&self, other: &Aabb) -> Aabb {
+    Aabb {
+      min: self.min.inf(&other.min),
+      max: self.max.sup(&other.max),
+    }
+  }
+
+  pub fn centroid(&self) -> Point3<f32> {
+    na::center(&self.min, &self.max)
+  }
+
+  pub fn extent(&self) -> Vector3<f32> {
+    self.max - self.min
+  }
+
+  pub fn surface_area(&self) -> f32 {
+    let d = self.extent();
+    if d.x < 0. {
+      return 0.;
+    }
+    2. * (d.x * d.y + d.y * d.z + d.z * d.x)
+  }
+
+  // box around this one once it is moved, which stays axis-aligned
+  pub fn transform(&self, isometry: &Isometry3<f32>) -> Aabb {
+    let corners: Vec<Point3<f32>> = (0..8)
+      .map(|i| {
+        isometry.transform_point(&Point3::new(
+          if i & 1 == 0 { self.min.x } else { self.max.x },
+          if i & 2 == 0 { self.min.y } else { self.max.y },
+          if i & 4 == 0 { self.min.z } else { self.max.z },
+        ))
+      })
+      .collect();
+    Aabb::from_points(&corners)
+  }
+
+  // slab test: distance along the ray where it enters the box, if it does
+  // before `max_distance`
+  // https://www.scratchapixel.com/lessons/3d-basic-rendering/minimal-ray-tracer-rendering-simple-shapes/ray-box-intersection
+  pub fn intersect(&self, ray: &Ray, max_distance: f32) -> Option<f32> {
+    self.clip(ray, max_distance).map(|(enter, _)| enter)
+  }
+
+  // distances along the ray where it enters and leaves the box, within
+  // [0, max_distance]
+  pub fn clip(&self, ray: &Ray, max_distance: f32) -> Option<(f32, f32)> {
+    let mut t_min = 0f32;
+    let mut t_max = max_distance;
+    for axis in 0..3 {
+      let inverse_direction = 1. / ray.direction[axis];
+      let mut t0 = (self.min[axis] - ray.origin[axis]) * inverse_direction;
+      let mut t1 = (self.max[axis] - ray.origin[axis]) * inverse_direction;
+      if inverse_direction < 0. {
+        std::mem::swap(&mut t0, &mut t1);
+      }
+      // NaN (ray parallel to and on a slab boundary) keeps the current bound
+      t_min = if t0 > t_min { t0 } else { t_min };
+      t_max = if t1 < t_max { t1 } else { t_max };
+      if t_max < t_min {
+        return None;
+      }
+    }
+    Some((t_min, t_max))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use na::Unit;
+
+  #[test]
+  fn test_intersect() {
+    let aabb = Aabb::new(Point3::new(-1., -1., -1.), Point3::new(1., 1., 1.));
+    let ray = |origin: Point3<f32>, direction: Vector3<f32>| Ray {
+      origin,
+      direction: Unit::new_normalize(direction),
+    };
+    let front = Vector3::new(0., 0., 1.);
+
+    assert_eq!(
+      aabb.intersect(&ray(Point3::new(0., 0., -10.), front), f32::INFINITY),
+      Some(9.)
+    );
+    // starting inside
+    assert_eq!(
+      aabb.intersect(&ray(Point3::new(0., 0., 0.), front), f32::INFINITY),
+      Some(0.)
+    );
+    // too far away
+    assert_eq!(
+      aabb.intersect(&ray(Point3::new(0., 0., -10.), front), 5.),
+      None
+    );
+    // behind
+    assert_eq!(
+      aabb.intersect(&ray(Point3::new(0., 0., 10.), front), f32::INFINITY),
+      None
+    );
+    // flat box, ray parallel to its plane
+    let flat = Aabb::new(Point3::new(-1., 0., -1.), Point3::new(1., 0., 1.));
+    assert!(flat
+      .intersect(&ray(Point3::new(0., 1., -10.), front), f32::INFINITY)
+      .is_none());
+    assert!(flat
+      .intersect(
+        &ray(Point3::new(0., 1., -10.), Vector3::new(0., -1., 10.)),
+        f32::INFINITY
+      )
+      .is_some());
+  }
+}
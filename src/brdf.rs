@@ -0,0 +1,215 @@
+use crate::color::Color;
+use crate::ops::reflect;
+use crate::sampling::{cosine_sample_hemisphere, orthonormal_basis};
+use na::{Unit, Vector3};
+use rand::Rng;
+use std::f32::consts::PI;
+
+// Metallic-roughness microfacet BRDF, as in glTF: a lambertian base under a
+// GGX specular lobe with Smith shadowing and Schlick's Fresnel. Metals have
+// no diffuse part and their specular reflection is tinted by the base color.
+// https://www.khronos.org/registry/glTF/specs/2.0/glTF-2.0.html#appendix-b-brdf-implementation
+// https://google.github.io/filament/Filament.html#materialsystem/specularbrdf
+#[derive(Debug, Copy, Clone)]
+pub struct Microfacet {
+  pub base_color: Color,
+  pub metallic: f32,
+  pub roughness: f32,
+  // reflectance of dielectrics facing the viewer, 0.5 giving 4%
+  pub specular: f32,
+}
+
+// below this, the lobe is so thin that floats can't represent it
+const MIN_ALPHA: f32 = 1e-3;
+
+fn lerp(a: Color, b: Color, t: f32) -> Color {
+  a * (1. - t) + b * t
+}
+
+// GGX / Trowbridge-Reitz normal distribution
+fn distribution(n_dot_h: f32, alpha: f32) -> f32 {
+  let alpha2 = alpha * alpha;
+  let d = n_dot_h * n_dot_h * (alpha2 - 1.) + 1.;
+  alpha2 / (PI * d * d)
+}
+
+// Smith's masking for a single direction
+fn smith_g1(n_dot_x: f32, alpha: f32) -> f32 {
+  let alpha2 = alpha * alpha;
+  2. * n_dot_x / (n_dot_x + (alpha2 + (1. - alpha2) * n_dot_x * n_dot_x).sqrt())
+}
+
+pub fn schlick_fresnel(f0: Color, cos: f32) -> Color {
+  let t = (1. - cos).max(0.).powi(5);
+  f0 + (Color::new(1., 1., 1.) - f0) * t
+}
+
+// width of the GGX distribution for a perceptually linear roughness
+pub fn roughness_to_alpha(roughness: f32) -> f32 {
+  (roughness * roughness).max(MIN_ALPHA)
+}
+
+// normal of a microfacet around `normal`, with a density of D(h) (n · h)
+pub fn sample_ggx_normal(
+  normal: &Unit<Vector3<f32>>,
+  alpha: f32,
+  rng: &mut impl Rng,
+) -> Unit<Vector3<f32>> {
+  let (u1, u2): (f32, f32) = (rng.gen(), rng.gen());
+  let cos_theta = ((1. - u1) / (1. + (alpha * alpha - 1.) * u1)).sqrt();
+  let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+  let phi = 2. * PI * u2;
+  let (tangent, bitangent) = orthonormal_basis(normal);
+  Unit::new_normalize(
+    tangent * (sin_theta * phi.cos())
+      + bitangent * (sin_theta * phi.sin())
+      + normal.into_inner() * cos_theta,
+  )
+}
+
+impl Microfacet {
+  fn alpha(&self) -> f32 {
+    roughness_to_alpha(self.roughness)
+  }
+
+  fn dielectric_f0(&self) -> Color {
+    let f0 = 0.08 * self.specular;
+    Color::new(f0, f0, f0)
+  }
+
+  // reflectance at normal incidence
+  pub fn f0(&self) -> Color {
+    lerp(self.dielectric_f0(), self.base_color, self.metallic)
+  }
+
+  // light reflected towards `v` for light coming from `l`, per unit of
+  // irradiance; both point away from the surface
+  pub fn evaluate(
+    &self,
+    normal: &Unit<Vector3<f32>>,
+    v: &Unit<Vector3<f32>>,
+    l: &Unit<Vector3<f32>>,
+  ) -> Color {
+    let (diffuse, specular) = self.lobes(normal, v, l);
+    diffuse + specular
+  }
+
+  // `evaluate` split into the light scattered below the surface and the
+  // light reflected by it
+  pub fn lobes(
+    &self,
+    normal: &Unit<Vector3<f32>>,
+    v: &Unit<Vector3<f32>>,
+    l: &Unit<Vector3<f32>>,
+  ) -> (Color, Color) {
+    let n_dot_v = normal.dot(v);
+    let n_dot_l = normal.dot(l);
+    if n_dot_v <= 0. || n_dot_l <= 0. {
+      return (Color::zero(), Color::zero());
+    }
+    let h = Unit::new_normalize(v.into_inner() + l.into_inner());
+    let alpha = self.alpha();
+    let v_dot_h = v.dot(&h);
+    let fresnel = schlick_fresnel(self.f0(), v_dot_h);
+    let specular = fresnel
+      * (distribution(normal.dot(&h), alpha) * smith_g1(n_dot_v, alpha) * smith_g1(n_dot_l, alpha)
+        / (4. * n_dot_v * n_dot_l));
+    // what the dielectric part doesn't reflect at its surface scatters below
+    let dielectric = schlick_fresnel(self.dielectric_f0(), v_dot_h);
+    let diffuse =
+      (Color::new(1., 1., 1.) - dielectric) * self.base_color * ((1. - self.metallic) / PI);
+    (diffuse, specular)
+  }
+
+  // chance of sampling the specular lobe rather than the diffuse one
+  fn specular_probability(&self) -> f32 {
+    0.5 * (1. + self.metallic)
+  }
+
+  // probability density of `sample` returning `l`
+  pub fn pdf(
+    &self,
+    normal: &Unit<Vector3<f32>>,
+    v: &Unit<Vector3<f32>>,
+    l: &Unit<Vector3<f32>>,
+  ) -> f32 {
+    let n_dot_l = normal.dot(l);
+    if n_dot_l <= 0. {
+      return 0.;
+    }
+    let h = Unit::new_normalize(v.into_inner() + l.into_inner());
+    let n_dot_h = normal.dot(&h);
+    let specular = distribution(n_dot_h, self.alpha()) * n_dot_h / (4. * v.dot(&h).abs());
+    let diffuse = n_dot_l / PI;
+    let p = self.specular_probability();
+    p * specular + (1. - p) * diffuse
+  }
+
+  // direction for the next bounce of a path arriving from `v`, with the
+  // BRDF times the cosine over the probability density of picking it; None
+  // when it would go below the surface
+  pub fn sample(
+    &self,
+    normal: &Unit<Vector3<f32>>,
+    v: &Unit<Vector3<f32>>,
+    rng: &mut impl Rng,
+  ) -> Option<(Unit<Vector3<f32>>, Color)> {
+    let l = if rng.gen::<f32>() < self.specular_probability() {
+      let h = sample_ggx_normal(normal, self.alpha(), rng);
+      Unit::new_normalize(reflect(v, &h))
+    } else {
+      cosine_sample_hemisphere(normal, rng)
+    };
+    let n_dot_l = normal.dot(&l);
+    let pdf = self.pdf(normal, v, &l);
+    if n_dot_l <= 0. || pdf <= 0. {
+      return None;
+    }
+    Some((l, self.evaluate(normal, v, &l) * (n_dot_l / pdf)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+
+  #[test]
+  fn test_microfacet() {
+    let normal = Vector3::z_axis();
+    let v = Unit::new_normalize(Vector3::new(0.3, 0., 1.));
+    let l = Unit::new_normalize(Vector3::new(-0.5, 0.2, 1.));
+    let mut rng = StdRng::seed_from_u64(0);
+    for &(metallic, roughness) in &[(0., 0.5), (1., 0.6), (0.5, 0.9), (1., 1.)] {
+      let brdf = Microfacet {
+        base_color: Color::new(1., 1., 1.),
+        metallic,
+        roughness,
+        specular: 0.5,
+      };
+      // reciprocity
+      let forth = brdf.evaluate(&normal, &v, &l);
+      let back = brdf.evaluate(&normal, &l, &v);
+      assert!((forth.r - back.r).abs() < 1e-5);
+      // importance sampling agrees with uniform sampling of the hemisphere
+      let count = 50000;
+      let sampled = (0..count)
+        .filter_map(|_| brdf.sample(&normal, &v, &mut rng))
+        .fold(0., |sum, (_, weight)| sum + weight.r)
+        / count as f32;
+      let uniform = (0..count)
+        .map(|_| {
+          let (z, phi): (f32, f32) = (rng.gen(), rng.gen::<f32>() * 2. * PI);
+          let radius = (1. - z * z).sqrt();
+          let l = Unit::new_normalize(Vector3::new(radius * phi.cos(), radius * phi.sin(), z));
+          brdf.evaluate(&normal, &v, &l).r * z * 2. * PI
+        })
+        .sum::<f32>()
+        / count as f32;
+      // a white surface reflects at most what it receives
+      assert!(sampled < 1.01);
+      assert!((sampled - uniform).abs() < 0.03, "{} {}", sampled, uniform);
+    }
+  }
+}
@@ -0,0 +1,244 @@
+use crate::aabb::Aabb;
+use crate::ray::Ray;
+use crate::shapes::{get_nearest_cast_info, CastInfo};
+
+// Bounding volume hierarchy over anything with a bounding box, built with
+// the surface area heuristic. It only stores indices: whoever owns the
+// primitives decides what casting a ray to index `i` means.
+// https://pbr-book.org/3ed-2018/Primitives_and_Intersection_Acceleration/Bounding_Volume_Hierarchies
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+  nodes: Vec<BvhNode>,
+  indices: Vec<usize>,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum BvhNode {
+  Leaf {
+    bounds: Aabb,
+    first: usize,
+    count: usize,
+  },
+  // the left child always comes right after its parent
+  Interior {
+    bounds: Aabb,
+    right: usize,
+  },
+}
+
+impl BvhNode {
+  fn bounds(&self) -> &Aabb {
+    match self {
+      BvhNode::Leaf { bounds, .. } => bounds,
+      BvhNode::Interior { bounds, .. } => bounds,
+    }
+  }
+}
+
+const BUCKETS: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+// cost of visiting an interior node, relative to casting to one primitive
+const TRAVERSAL_COST: f32 = 0.125;
+
+struct BuildItem {
+  index: usize,
+  bounds: Aabb,
+  centroid: na::Point3<f32>,
+}
+
+impl Bvh {
+  pub fn build(bounds: &[Aabb]) -> Bvh {
+    let mut items: Vec<BuildItem> = bounds
+      .iter()
+      .enumerate()
+      .map(|(index, bounds)| BuildItem {
+        index,
+        bounds: *bounds,
+        centroid: bounds.centroid(),
+      })
+      .collect();
+    let mut bvh = Bvh {
+      nodes: Vec::with_capacity(2 * items.len()),
+      indices: Vec::with_capacity(items.len()),
+    };
+    if !items.is_empty() {
+      bvh.build_node(&mut items);
+    }
+    bvh
+  }
+
+  pub fn map_indices(mut self, f: impl Fn(usize) -> usize) -> Bvh {
+    for index in self.indices.iter_mut() {
+      *index = f(*index);
+    }
+    self
+  }
+
+  fn push_leaf(&mut self, items: &[BuildItem], bounds: Aabb) -> usize {
+    self.nodes.push(BvhNode::Leaf {
+      bounds,
+      first: self.indices.len(),
+      count: items.len(),
+    });
+    self.indices.extend(items.iter().map(|item| item.index));
+    self.nodes.len() - 1
+  }
+
+  fn build_node(&mut self, items: &mut [BuildItem]) -> usize {
+    let bounds = items
+      .iter()
+      .fold(Aabb::empty(), |aabb, item| aabb.union(&item.bounds));
+    if items.len() == 1 {
+      return self.push_leaf(items, bounds);
+    }
+
+    let centroid_bounds = Aabb::from_points(items.iter().map(|item| &item.centroid));
+    let extent = centroid_bounds.extent();
+    let axis = extent.imax();
+    if extent[axis] <= 0. {
+      // every centroid in the same place, nothing to split on
+      return self.push_leaf(items, bounds);
+    }
+
+    // bin centroids along the longest axis, then pick the cheapest boundary
+    let bucket_of = |item: &BuildItem| {
+      let offset = (item.centroid[axis] - centroid_bounds.min[axis]) / extent[axis];
+      ((offset * BUCKETS as f32) as usize).min(BUCKETS - 1)
+    };
+    let mut buckets = [(0usize, Aabb::empty()); BUCKETS];
+    for item in items.iter() {
+      let bucket = &mut buckets[bucket_of(item)];
+      bucket.0 += 1;
+      bucket.1 = bucket.1.union(&item.bounds);
+    }
+    let side_cost = |side: &[(usize, Aabb)]| {
+      let (count, aabb) = side
+        .iter()
+        .fold((0, Aabb::empty()), |(count, aabb), (n, b)| {
+          (count + n, aabb.union(b))
+        });
+      count as f32 * aabb.surface_area()
+    };
+    let (split, split_cost) = (1..BUCKETS)
+      .map(|split| {
+        let cost = TRAVERSAL_COST
+          + (side_cost(&buckets[..split]) + side_cost(&buckets[split..])) / bounds.surface_area();
+        (split, cost)
+      })
+      .fold((0, f32::INFINITY), |best, candidate| {
+        if candidate.1 < best.1 {
+          candidate
+        } else {
+          best
+        }
+      });
+
+    let leaf_cost = items.len() as f32;
+    if items.len() <= MAX_LEAF_SIZE && leaf_cost <= split_cost {
+      return self.push_leaf(items, bounds);
+    }
+
+    items.sort_by_key(|item| bucket_of(item) >= split);
+    let middle = items.iter().position(|item| bucket_of(item) >= split);
+    let middle = match middle {
+      Some(m) if m > 0 => m,
+      // the heuristic found no boundary with items on both sides
+      _ => {
+        items.sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+        items.len() / 2
+      }
+    };
+
+    let node = self.nodes.len();
+    self.nodes.push(BvhNode::Interior { bounds, right: 0 });
+    let (left_items, right_items) = items.split_at_mut(middle);
+    self.build_node(left_items);
+    let right = self.build_node(right_items);
+    self.nodes[node] = BvhNode::Interior { bounds, right };
+    node
+  }
+
+  // nearest hit among the primitives whose boxes the ray goes through,
+  // `cast` being called with their indices
+  pub fn cast<'a>(
+    &self,
+    ray: &Ray,
+    mut cast: impl FnMut(usize) -> Option<CastInfo<'a>>,
+  ) -> Option<CastInfo<'a>> {
+    let mut nearest: Option<CastInfo<'a>> = None;
+    if self.nodes.is_empty() {
+      return nearest;
+    }
+    let mut stack = vec![0];
+    while let Some(node) = stack.pop() {
+      let max_distance = nearest.map_or(f32::INFINITY, |info| info.distance);
+      if self.nodes[node]
+        .bounds()
+        .intersect(ray, max_distance)
+        .is_none()
+      {
+        continue;
+      }
+      match self.nodes[node] {
+        BvhNode::Leaf { first, count, .. } => {
+          for &index in &self.indices[first..first + count] {
+            nearest = get_nearest_cast_info(nearest, cast(index));
+          }
+        }
+        BvhNode::Interior { right, .. } => {
+          stack.push(right);
+          stack.push(node + 1);
+        }
+      }
+    }
+    nearest
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::shapes::test_helpers::material;
+  use crate::shapes::{sphere::Sphere, Castable};
+  use na::{Point3, Unit, Vector3};
+
+  #[test]
+  fn test_cast_matches_linear_search() {
+    let spheres: Vec<Sphere> = (0..200)
+      .map(|i| {
+        let (x, y, z) = ((i % 7) as f32, ((i / 7) % 5) as f32, (i / 35) as f32);
+        Sphere::new(
+          Point3::new(x * 1.7 - 5., y * 1.3 - 3., -z * 2.1),
+          0.3 + (i % 3) as f32 * 0.25,
+          material(),
+        )
+      })
+      .collect();
+    let bounds: Vec<Aabb> = spheres
+      .iter()
+      .map(|sphere| sphere.bounding_box().unwrap())
+      .collect();
+    let bvh = Bvh::build(&bounds);
+
+    for i in 0..500 {
+      let angle = i as f32 * 0.37;
+      let ray = Ray {
+        origin: Point3::new(angle.sin() * 3., angle.cos() * 2., 4.),
+        direction: Unit::new_normalize(Vector3::new(
+          (angle * 1.3).cos() * 0.6,
+          (angle * 0.7).sin() * 0.5,
+          -1.,
+        )),
+      };
+      let linear = spheres
+        .iter()
+        .map(|sphere| sphere.cast_ray(&ray))
+        .fold(None, get_nearest_cast_info);
+      let accelerated = bvh.cast(&ray, |index| spheres[index].cast_ray(&ray));
+      assert_eq!(
+        linear.map(|info| info.distance),
+        accelerated.map(|info| info.distance)
+      );
+    }
+  }
+}
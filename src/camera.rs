@@ -0,0 +1,310 @@
+use crate::ray::Ray;
+use crate::sampling::{concentric_sample_disk, sample_polygon};
+use na::{Isometry3, Point3, Unit, Vector3};
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::sync::Arc;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Resolution {
+  pub width: u32,
+  pub height: u32,
+}
+
+impl Resolution {
+  pub fn aspect_ratio(&self) -> f32 {
+    self.width as f32 / self.height as f32
+  }
+
+  // (-1, 1) at the top left corner, (1, -1) at the bottom right
+  fn screen_coordinates(&self, (x, y): (f32, f32)) -> (f32, f32) {
+    (
+      2. * x / self.width as f32 - 1.,
+      1. - 2. * y / self.height as f32,
+    )
+  }
+}
+
+// How rays leave the camera. Rays are generated in camera space, looking
+// towards -z with y up, then placed in the world by the camera's pose so that
+// the controls work the same whatever the lens.
+pub trait Camera: std::fmt::Debug {
+  // ray through a point of the image, in pixels from the top left corner;
+  // None when the lens doesn't cover that point. `lens` is a point of the
+  // unit square picking where the ray crosses the aperture, (0.5, 0.5)
+  // being its center.
+  fn generate_camera_ray(
+    &self,
+    point: (f32, f32),
+    lens: (f32, f32),
+    resolution: &Resolution,
+  ) -> Option<Ray>;
+
+  fn generate_ray(
+    &self,
+    camera_to_world: &Isometry3<f32>,
+    point: (f32, f32),
+    lens: (f32, f32),
+    resolution: &Resolution,
+  ) -> Option<Ray> {
+    self
+      .generate_camera_ray(point, lens, resolution)
+      .map(|ray| ray.apply_isometry(*camera_to_world))
+  }
+
+  // the same camera with its plane of focus `distance` away from the eye,
+  // for cameras that have one
+  fn focused_at(&self, _distance: f32) -> Option<Arc<dyn Camera + Send + Sync>> {
+    None
+  }
+}
+
+fn ray_from_eye(direction: Vector3<f32>) -> Ray {
+  Ray {
+    origin: Point3::origin(),
+    direction: Unit::new_normalize(direction),
+  }
+}
+
+// A thin lens: rays leave from a point of the aperture and converge on the
+// plane of focus, blurring what is in front of or behind it. A pinhole when
+// the aperture is zero.
+// https://pbr-book.org/3ed-2018/Camera_Models/Projective_Camera_Models#TheThinLensModelandDepthofField
+#[derive(Debug, Copy, Clone)]
+pub struct Perspective {
+  // vertical, in radians
+  pub fov: f32,
+  // radius of the lens, in world units
+  pub aperture: f32,
+  pub focus_distance: f32,
+  // the aperture is a regular polygon with this many blades, giving out of
+  // focus highlights their shape; a disk below 3
+  pub blades: u32,
+}
+
+impl Camera for Perspective {
+  fn generate_camera_ray(
+    &self,
+    point: (f32, f32),
+    lens: (f32, f32),
+    resolution: &Resolution,
+  ) -> Option<Ray> {
+    let (x, y) = resolution.screen_coordinates(point);
+    let half_height = (self.fov / 2.).tan();
+    let direction = Vector3::new(
+      x * half_height * resolution.aspect_ratio(),
+      y * half_height,
+      -1.,
+    );
+    if self.aperture <= 0. {
+      return Some(ray_from_eye(direction));
+    }
+
+    let (lens_x, lens_y) = if self.blades >= 3 {
+      // a vertex at the top
+      sample_polygon(self.blades, FRAC_PI_2, lens)
+    } else {
+      concentric_sample_disk(lens)
+    };
+    let origin = Point3::new(lens_x * self.aperture, lens_y * self.aperture, 0.);
+    // where the ray through the center of the lens meets the plane of focus
+    let focus = Point3::from(direction * self.focus_distance);
+    Some(Ray {
+      origin,
+      direction: Unit::new_normalize(focus - origin),
+    })
+  }
+
+  fn focused_at(&self, distance: f32) -> Option<Arc<dyn Camera + Send + Sync>> {
+    Some(Arc::new(Perspective {
+      focus_distance: distance,
+      ..*self
+    }))
+  }
+}
+
+// parallel rays, from a rectangle centered on the eye
+#[derive(Debug, Copy, Clone)]
+pub struct Orthographic {
+  // of the rectangle, in world units
+  pub height: f32,
+}
+
+impl Camera for Orthographic {
+  fn generate_camera_ray(
+    &self,
+    point: (f32, f32),
+    _lens: (f32, f32),
+    resolution: &Resolution,
+  ) -> Option<Ray> {
+    let (x, y) = resolution.screen_coordinates(point);
+    let half_height = self.height / 2.;
+    Some(Ray {
+      origin: Point3::new(
+        x * half_height * resolution.aspect_ratio(),
+        y * half_height,
+        0.,
+      ),
+      direction: -Vector3::z_axis(),
+    })
+  }
+}
+
+// equidistant fisheye: the angle to the view direction grows linearly with
+// the distance to the image center, reaching fov / 2 on the largest circle
+// that fits the image
+#[derive(Debug, Copy, Clone)]
+pub struct Fisheye {
+  // in radians, up to 2π
+  pub fov: f32,
+}
+
+impl Camera for Fisheye {
+  fn generate_camera_ray(
+    &self,
+    point: (f32, f32),
+    _lens: (f32, f32),
+    resolution: &Resolution,
+  ) -> Option<Ray> {
+    let (mut x, mut y) = resolution.screen_coordinates(point);
+    let aspect_ratio = resolution.aspect_ratio();
+    if aspect_ratio > 1. {
+      x *= aspect_ratio;
+    } else {
+      y /= aspect_ratio;
+    }
+    let radius = (x * x + y * y).sqrt();
+    if radius > 1. {
+      return None;
+    }
+    let theta = radius * self.fov / 2.;
+    let phi = y.atan2(x);
+    Some(ray_from_eye(Vector3::new(
+      theta.sin() * phi.cos(),
+      theta.sin() * phi.sin(),
+      -theta.cos(),
+    )))
+  }
+}
+
+// the whole sphere around the eye, longitude along x and latitude along y
+#[derive(Debug, Copy, Clone)]
+pub struct Equirectangular;
+
+impl Camera for Equirectangular {
+  fn generate_camera_ray(
+    &self,
+    point: (f32, f32),
+    _lens: (f32, f32),
+    resolution: &Resolution,
+  ) -> Option<Ray> {
+    let (x, y) = resolution.screen_coordinates(point);
+    let longitude = x * PI;
+    let latitude = y * FRAC_PI_2;
+    Some(ray_from_eye(Vector3::new(
+      latitude.cos() * longitude.sin(),
+      latitude.sin(),
+      -latitude.cos() * longitude.cos(),
+    )))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const CENTER: (f32, f32) = (0.5, 0.5);
+
+  fn pinhole(fov: f32) -> Perspective {
+    Perspective {
+      fov,
+      aperture: 0.,
+      focus_distance: 1.,
+      blades: 0,
+    }
+  }
+
+  fn angle_to_view(ray: &Ray) -> f32 {
+    ray.direction.angle(&-Vector3::z())
+  }
+
+  #[test]
+  fn test_generate_camera_ray() {
+    let resolution = Resolution {
+      width: 200,
+      height: 100,
+    };
+    let center = (100., 50.);
+    let right_edge = (200., 50.);
+    let top_edge = (100., 0.);
+
+    let perspective = pinhole(PI / 2.);
+    let ray = perspective
+      .generate_camera_ray(top_edge, CENTER, &resolution)
+      .unwrap();
+    assert!((angle_to_view(&ray) - PI / 4.).abs() < 1e-5);
+    let ray = perspective
+      .generate_camera_ray(right_edge, CENTER, &resolution)
+      .unwrap();
+    assert!((angle_to_view(&ray) - 2f32.atan()).abs() < 1e-5);
+
+    let orthographic = Orthographic { height: 4. };
+    let ray = orthographic
+      .generate_camera_ray((0., 0.), CENTER, &resolution)
+      .unwrap();
+    assert_eq!(ray.origin, Point3::new(-4., 2., 0.));
+    assert_eq!(ray.direction, -Vector3::z_axis());
+
+    let fisheye = Fisheye { fov: PI };
+    let ray = fisheye
+      .generate_camera_ray(top_edge, CENTER, &resolution)
+      .unwrap();
+    assert!((angle_to_view(&ray) - PI / 2.).abs() < 1e-5);
+    assert!(fisheye
+      .generate_camera_ray((0., 0.), CENTER, &resolution)
+      .is_none());
+
+    let equirectangular = Equirectangular;
+    let ray = equirectangular
+      .generate_camera_ray(center, CENTER, &resolution)
+      .unwrap();
+    assert!(angle_to_view(&ray) < 1e-5);
+    let ray = equirectangular
+      .generate_camera_ray(right_edge, CENTER, &resolution)
+      .unwrap();
+    assert!((angle_to_view(&ray) - PI).abs() < 1e-3);
+    let ray = equirectangular
+      .generate_camera_ray(top_edge, CENTER, &resolution)
+      .unwrap();
+    assert!((ray.direction.y - 1.).abs() < 1e-5);
+  }
+
+  #[test]
+  fn test_thin_lens() {
+    let resolution = Resolution {
+      width: 200,
+      height: 100,
+    };
+    let camera = Perspective {
+      fov: PI / 2.,
+      aperture: 0.5,
+      focus_distance: 4.,
+      blades: 6,
+    };
+    let pinhole = pinhole(PI / 2.);
+    for &point in &[(100., 50.), (13., 87.)] {
+      let through_center = pinhole
+        .generate_camera_ray(point, CENTER, &resolution)
+        .unwrap();
+      let on_focus_plane =
+        |ray: &Ray| ray.origin + ray.direction.into_inner() * (-4. / ray.direction.z);
+      // every ray through the lens meets on the plane of focus
+      for &lens in &[(0., 0.), (0.9, 0.2), (0.5, 1.)] {
+        let ray = camera
+          .generate_camera_ray(point, lens, &resolution)
+          .unwrap();
+        assert!(ray.origin.coords.norm() <= 0.5 + 1e-5);
+        assert!((on_focus_plane(&ray) - on_focus_plane(&through_center)).norm() < 1e-4);
+      }
+    }
+  }
+}
@@ -1,21 +1,58 @@
+use image::Rgb;
 use sdl2::pixels::Color as SdlColor;
-use std::ops::{Add, AddAssign, Mul};
+use std::ops::{Add, AddAssign, Mul, Sub};
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+// linear radiance, unbounded: only turned into something displayable by
+// a `display::DisplayTransform` once a frame is done
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Color {
-  r: u8,
-  g: u8,
-  b: u8,
+  pub r: f32,
+  pub g: f32,
+  pub b: f32,
 }
 
 impl Color {
-  #[allow(non_snake_case)]
-  pub const fn RGB(r: u8, g: u8, b: u8) -> Color {
+  pub const fn new(r: f32, g: f32, b: f32) -> Color {
     Color { r, g, b }
   }
 
   pub const fn zero() -> Color {
-    Color::RGB(0, 0, 0)
+    Color::new(0., 0., 0.)
+  }
+
+  // sRGB encoded values, as picked in a color picker, decoded to linear
+  pub fn from_srgb(r: f32, g: f32, b: f32) -> Color {
+    Color::new(r, g, b).map(srgb_to_linear)
+  }
+
+  pub fn map(self, f: impl Fn(f32) -> f32) -> Color {
+    Color::new(f(self.r), f(self.g), f(self.b))
+  }
+
+  pub fn max_component(&self) -> f32 {
+    self.r.max(self.g).max(self.b)
+  }
+
+  // perceived brightness, with the Rec. 709 primaries of sRGB
+  pub fn luminance(&self) -> f32 {
+    0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+  }
+}
+
+// https://en.wikipedia.org/wiki/SRGB#Transformation
+pub fn srgb_to_linear(x: f32) -> f32 {
+  if x <= 0.04045 {
+    x / 12.92
+  } else {
+    ((x + 0.055) / 1.055).powf(2.4)
+  }
+}
+
+pub fn linear_to_srgb(x: f32) -> f32 {
+  if x <= 0.0031308 {
+    x * 12.92
+  } else {
+    1.055 * x.powf(1. / 2.4) - 0.055
   }
 }
 
@@ -23,20 +60,30 @@ impl Mul<f32> for Color {
   type Output = Self;
 
   fn mul(self, rhs: f32) -> Self {
-    let times = |x| ((x as f32) * rhs).min(255.) as u8;
-    Color::RGB(times(self.r), times(self.g), times(self.b))
+    self.map(|x| x * rhs)
+  }
+}
+
+// component-wise, e.g. light filtered by a surface
+impl Mul for Color {
+  type Output = Self;
+
+  fn mul(self, rhs: Self) -> Self {
+    Color::new(self.r * rhs.r, self.g * rhs.g, self.b * rhs.b)
   }
 }
 
 impl Add for Color {
   type Output = Self;
   fn add(self, rhs: Self) -> Self {
-    let plus = |a: u8, b: u8| ((a as u16) + (b as u16)).min(255).max(0) as u8;
-    Color::RGB(
-      plus(self.r, rhs.r),
-      plus(self.g, rhs.g),
-      plus(self.b, rhs.b),
-    )
+    Color::new(self.r + rhs.r, self.g + rhs.g, self.b + rhs.b)
+  }
+}
+
+impl Sub for Color {
+  type Output = Self;
+  fn sub(self, rhs: Self) -> Self {
+    Color::new(self.r - rhs.r, self.g - rhs.g, self.b - rhs.b)
   }
 }
 
@@ -46,14 +93,22 @@ impl AddAssign for Color {
   }
 }
 
-impl Into<SdlColor> for Color {
-  fn into(self) -> SdlColor {
-    SdlColor::RGB(self.r, self.g, self.b)
+// 8 bits per channel color, ready to be shown or written to a file
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DisplayColor {
+  pub r: u8,
+  pub g: u8,
+  pub b: u8,
+}
+
+impl From<DisplayColor> for SdlColor {
+  fn from(color: DisplayColor) -> SdlColor {
+    SdlColor::RGB(color.r, color.g, color.b)
   }
 }
 
-impl From<SdlColor> for Color {
-  fn from(raw: SdlColor) -> Color {
-    Color::RGB(raw.r, raw.g, raw.b)
+impl From<DisplayColor> for Rgb<u8> {
+  fn from(color: DisplayColor) -> Rgb<u8> {
+    Rgb([color.r, color.g, color.b])
   }
 }
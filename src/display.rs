@@ -0,0 +1,179 @@
+use crate::color::{linear_to_srgb, Color, DisplayColor};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ToneMapping {
+  // anything brighter than 1.0 is cut off
+  Clamp,
+  // x / (1 + x), per channel
+  Reinhard,
+  // https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+  Aces,
+}
+
+impl ToneMapping {
+  pub fn from_name(name: &str) -> Option<ToneMapping> {
+    match name {
+      "clamp" => Some(ToneMapping::Clamp),
+      "reinhard" => Some(ToneMapping::Reinhard),
+      "aces" => Some(ToneMapping::Aces),
+      _ => None,
+    }
+  }
+
+  fn apply(&self, x: f32) -> f32 {
+    match self {
+      ToneMapping::Clamp => x,
+      ToneMapping::Reinhard => x / (1. + x),
+      ToneMapping::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+    }
+  }
+}
+
+// encoding applied to tone mapped values before quantizing them to 8 bits
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TransferFunction {
+  Srgb,
+  Gamma(f32),
+  // values written as they are, e.g. for data that is post-processed later
+  Linear,
+}
+
+impl TransferFunction {
+  // "srgb", "linear" or a gamma exponent like "2.2"
+  pub fn from_name(name: &str) -> Option<TransferFunction> {
+    match name {
+      "srgb" => Some(TransferFunction::Srgb),
+      "linear" => Some(TransferFunction::Linear),
+      _ => name
+        .parse()
+        .ok()
+        .filter(|gamma: &f32| *gamma > 0.)
+        .map(TransferFunction::Gamma),
+    }
+  }
+
+  fn apply(&self, x: f32) -> f32 {
+    match self {
+      TransferFunction::Srgb => linear_to_srgb(x),
+      TransferFunction::Gamma(gamma) => x.powf(1. / gamma),
+      TransferFunction::Linear => x,
+    }
+  }
+}
+
+// how linear radiance coming out of the renderer becomes a display color
+// for a given render target
+#[derive(Debug, Copy, Clone)]
+pub struct DisplayTransform {
+  pub tone_mapping: ToneMapping,
+  // in stops: every +1 doubles the radiance before tone mapping
+  pub exposure: f32,
+  pub transfer: TransferFunction,
+}
+
+impl Default for DisplayTransform {
+  fn default() -> Self {
+    DisplayTransform {
+      tone_mapping: ToneMapping::Clamp,
+      exposure: 0.,
+      transfer: TransferFunction::Srgb,
+    }
+  }
+}
+
+impl DisplayTransform {
+  pub fn apply(&self, radiance: Color) -> DisplayColor {
+    let scale = self.exposure.exp2();
+    let to_u8 = |x: f32| {
+      let mapped = self.tone_mapping.apply(x.max(0.) * scale).clamp(0., 1.);
+      (self.transfer.apply(mapped) * 255.).round() as u8
+    };
+    DisplayColor {
+      r: to_u8(radiance.r),
+      g: to_u8(radiance.g),
+      b: to_u8(radiance.b),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_tone_mapping() {
+    let bright = Color::new(0.5, 1., 4.);
+    let clamp = DisplayTransform {
+      tone_mapping: ToneMapping::Clamp,
+      exposure: 0.,
+      transfer: TransferFunction::Linear,
+    };
+    assert_eq!(
+      clamp.apply(bright),
+      DisplayColor {
+        r: 128,
+        g: 255,
+        b: 255
+      }
+    );
+
+    let reinhard = DisplayTransform {
+      tone_mapping: ToneMapping::Reinhard,
+      exposure: 0.,
+      transfer: TransferFunction::Linear,
+    };
+    assert_eq!(
+      reinhard.apply(bright),
+      DisplayColor {
+        r: 85,
+        g: 128,
+        b: 204
+      }
+    );
+
+    // one stop down halves the radiance
+    let darker = DisplayTransform {
+      tone_mapping: ToneMapping::Clamp,
+      exposure: -1.,
+      transfer: TransferFunction::Linear,
+    };
+    assert_eq!(
+      darker.apply(Color::new(1., 1., 1.)),
+      clamp.apply(Color::new(0.5, 0.5, 0.5))
+    );
+
+    let aces = DisplayTransform {
+      tone_mapping: ToneMapping::Aces,
+      exposure: 0.,
+      transfer: TransferFunction::Linear,
+    };
+    assert_eq!(aces.apply(Color::zero()).r, 0);
+    assert_eq!(aces.apply(Color::new(1000., 1000., 1000.)).r, 255);
+  }
+
+  #[test]
+  fn test_transfer_function() {
+    let display = |transfer| DisplayTransform {
+      transfer,
+      ..DisplayTransform::default()
+    };
+    // an sRGB input color comes back out unchanged through an sRGB target
+    let picked = Color::from_srgb(59. / 255., 172. / 255., 214. / 255.);
+    assert_eq!(
+      display(TransferFunction::Srgb).apply(picked),
+      DisplayColor {
+        r: 59,
+        g: 172,
+        b: 214
+      }
+    );
+    assert_eq!(display(TransferFunction::Linear).apply(picked).g, 105);
+    assert_eq!(display(TransferFunction::Gamma(2.2)).apply(picked).g, 171);
+
+    assert_eq!(
+      TransferFunction::from_name("2.2"),
+      Some(TransferFunction::Gamma(2.2))
+    );
+    assert_eq!(TransferFunction::from_name("-1"), None);
+  }
+}
@@ -0,0 +1,340 @@
+use crate::color::Color;
+use crate::light::{Light, LightSample};
+use crate::sampling::SamplePattern;
+use image::codecs::hdr::HdrDecoder;
+use na::{Point3, Unit, Vector3};
+use rand::RngCore;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// An equirectangular image of everything around the scene, like a
+// panorama: longitude along x, the center of the image looking towards -z,
+// and latitude along y, the top row looking up. Rays leaving the scene see
+// it, and it lights the surfaces through `samples` shadow rays picked in
+// proportion to its brightness, so that the sun in a photo of the sky
+// doesn't take thousands of samples to find.
+// https://pbr-book.org/3ed-2018/Light_Sources/Infinite_Area_Lights
+#[derive(Debug, Clone)]
+pub struct EnvironmentMap {
+  width: u32,
+  height: u32,
+  // linear, row by row from the top
+  pixels: Vec<Color>,
+  // around the y axis, in radians
+  rotation: f32,
+  intensity: f32,
+  samples: u32,
+  // cumulative distribution of the brightness over the rows, then over the
+  // pixels of each row, from 0 to 1
+  row_distribution: Vec<f32>,
+  column_distributions: Vec<Vec<f32>>,
+  // of the weights of all the pixels, for the probability densities
+  average_weight: f32,
+}
+
+impl EnvironmentMap {
+  pub fn new(
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+    rotation: f32,
+    intensity: f32,
+    samples: u32,
+  ) -> Self {
+    let mut map = EnvironmentMap {
+      width,
+      height,
+      pixels,
+      rotation,
+      intensity,
+      samples,
+      row_distribution: vec![],
+      column_distributions: vec![],
+      average_weight: 0.,
+    };
+    let rows: Vec<Vec<f32>> = (0..height)
+      .map(|y| (0..width).map(|x| map.weight(x, y)).collect())
+      .collect();
+    let row_weights: Vec<f32> = rows.iter().map(|row| row.iter().sum()).collect();
+    map.average_weight = row_weights.iter().sum::<f32>() / (width * height) as f32;
+    map.row_distribution = cumulative_distribution(&row_weights);
+    map.column_distributions = rows
+      .iter()
+      .map(|row| cumulative_distribution(row))
+      .collect();
+    map
+  }
+
+  // Radiance HDR (.hdr) and OpenEXR (.exr) files hold linear light; other
+  // images are decoded from sRGB, and can't be brighter than white
+  pub fn load(path: &Path, rotation: f32, intensity: f32, samples: u32) -> Result<Self, String> {
+    let error = |e: &dyn std::fmt::Display| format!("could not read {}: {}", path.display(), e);
+    let extension = path
+      .extension()
+      .and_then(|extension| extension.to_str())
+      .map(str::to_lowercase);
+    let (width, height, pixels) = match extension.as_deref() {
+      Some("hdr") => {
+        let file = File::open(path).map_err(|e| error(&e))?;
+        let decoder = HdrDecoder::new(BufReader::new(file)).map_err(|e| error(&e))?;
+        let metadata = decoder.metadata();
+        let pixels = decoder
+          .read_image_hdr()
+          .map_err(|e| error(&e))?
+          .into_iter()
+          .map(|pixel| Color::new(pixel[0], pixel[1], pixel[2]))
+          .collect();
+        (metadata.width, metadata.height, pixels)
+      }
+      Some("exr") => {
+        let image = exr::prelude::read_first_rgba_layer_from_file(
+          path,
+          |resolution, _| {
+            (
+              resolution.width(),
+              vec![Color::zero(); resolution.width() * resolution.height()],
+            )
+          },
+          |(width, pixels): &mut (usize, Vec<Color>),
+           position,
+           (r, g, b, _): (f32, f32, f32, f32)| {
+            pixels[position.y() * *width + position.x()] = Color::new(r, g, b);
+          },
+        )
+        .map_err(|e| error(&e))?;
+        let size = image.layer_data.size;
+        let (_, pixels) = image.layer_data.channel_data.pixels;
+        (size.width() as u32, size.height() as u32, pixels)
+      }
+      _ => {
+        let image = image::open(path).map_err(|e| error(&e))?.into_rgb8();
+        let pixels = image
+          .pixels()
+          .map(|pixel| {
+            let [r, g, b] = pixel.0;
+            Color::from_srgb(r as f32 / 255., g as f32 / 255., b as f32 / 255.)
+          })
+          .collect();
+        (image.width(), image.height(), pixels)
+      }
+    };
+    if width == 0 || height == 0 {
+      return Err(format!("{} is empty", path.display()));
+    }
+    Ok(EnvironmentMap::new(
+      width, height, pixels, rotation, intensity, samples,
+    ))
+  }
+
+  fn pixel(&self, x: u32, y: u32) -> Color {
+    self.pixels[(y * self.width + x) as usize]
+  }
+
+  // latitude of the center of a row, from π / 2 at the top
+  fn row_latitude(&self, y: u32) -> f32 {
+    (0.5 - (y as f32 + 0.5) / self.height as f32) * PI
+  }
+
+  // how often a pixel is sampled: pixels near the poles cover less of the
+  // sphere than those at the equator
+  fn weight(&self, x: u32, y: u32) -> f32 {
+    self.pixel(x, y).luminance().max(0.) * self.row_latitude(y).cos()
+  }
+
+  // for a point of the image, in [0, 1]² from the top left corner
+  fn direction(&self, (u, v): (f32, f32)) -> Unit<Vector3<f32>> {
+    let longitude = (2. * u - 1.) * PI + self.rotation;
+    let latitude = (0.5 - v) * PI;
+    Unit::new_normalize(Vector3::new(
+      latitude.cos() * longitude.sin(),
+      latitude.sin(),
+      -latitude.cos() * longitude.cos(),
+    ))
+  }
+
+  fn image_point(&self, direction: &Unit<Vector3<f32>>) -> (f32, f32) {
+    let longitude = direction.x.atan2(-direction.z) - self.rotation;
+    let latitude = direction.y.clamp(-1., 1.).asin();
+    (
+      (0.5 * (longitude / PI + 1.)).rem_euclid(1.),
+      0.5 - latitude / PI,
+    )
+  }
+
+  // bilinearly filtered, wrapping around horizontally
+  fn filtered(&self, (u, v): (f32, f32)) -> Color {
+    // pixel centers are at half integers
+    let x = u * self.width as f32 - 0.5;
+    let y = v * self.height as f32 - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+    let column = |x: f32| (x as i64).rem_euclid(self.width as i64) as u32;
+    let row = |y: f32| (y.max(0.) as u32).min(self.height - 1);
+    let (x0, x1, y0, y1) = (column(x0), column(x0 + 1.), row(y0), row(y0 + 1.));
+    let top = self.pixel(x0, y0) * (1. - tx) + self.pixel(x1, y0) * tx;
+    let bottom = self.pixel(x0, y1) * (1. - tx) + self.pixel(x1, y1) * tx;
+    top * (1. - ty) + bottom * ty
+  }
+}
+
+// from 0 to 1, one more entry than there are weights; all zeros when the
+// weights are
+fn cumulative_distribution(weights: &[f32]) -> Vec<f32> {
+  let total: f32 = weights.iter().sum();
+  let mut sum = 0.;
+  let mut distribution = vec![0.];
+  for weight in weights {
+    sum += weight;
+    distribution.push(if total > 0. { sum / total } else { 0. });
+  }
+  distribution
+}
+
+// index of the interval of a cumulative distribution `t` falls in, and
+// where it falls in it
+fn sample_distribution(distribution: &[f32], t: f32) -> (usize, f32) {
+  let last = distribution.len() - 2;
+  let index = distribution[1..].partition_point(|&c| c <= t).min(last);
+  let (start, end) = (distribution[index], distribution[index + 1]);
+  let offset = if end > start {
+    ((t - start) / (end - start)).clamp(0., 1.)
+  } else {
+    0.5
+  };
+  (index, offset)
+}
+
+impl Light for EnvironmentMap {
+  fn samples(&self, _point: &Point3<f32>, mut rng: &mut dyn RngCore) -> Vec<LightSample> {
+    if self.average_weight <= 0. {
+      return vec![];
+    }
+    let count = self.samples.max(1);
+    SamplePattern::Jittered
+      .generate(count, 0, 0, &mut rng)
+      .into_iter()
+      .filter_map(|(sx, sy)| {
+        let (y, offset_y) = sample_distribution(&self.row_distribution, sy);
+        let (x, offset_x) = sample_distribution(&self.column_distributions[y], sx);
+        let (x, y) = (x as u32, y as u32);
+        let u = (x as f32 + offset_x) / self.width as f32;
+        let v = (y as f32 + offset_y) / self.height as f32;
+        // from a density over the image to one over directions
+        let cos_latitude = ((0.5 - v) * PI).cos();
+        if cos_latitude <= 1e-6 {
+          return None;
+        }
+        let pdf = self.weight(x, y) / self.average_weight / (2. * PI * PI * cos_latitude);
+        Some(LightSample {
+          direction: self.direction((u, v)),
+          distance: f32::INFINITY,
+          color: self.pixel(x, y),
+          intensity: self.intensity / (pdf * count as f32),
+          in_caustics: false,
+        })
+      })
+      .collect()
+  }
+
+  fn background(&self, direction: &Unit<Vector3<f32>>) -> Option<Color> {
+    Some(self.filtered(self.image_point(direction)) * self.intensity)
+  }
+
+  fn is_seen(&self) -> bool {
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+
+  fn pixel_at(map: &EnvironmentMap, (u, v): (f32, f32)) -> (u32, u32) {
+    (
+      ((u * map.width as f32) as u32).min(map.width - 1),
+      ((v * map.height as f32) as u32).min(map.height - 1),
+    )
+  }
+
+  #[test]
+  fn test_environment_map() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let origin = Point3::origin();
+    let (width, height) = (16, 8);
+    // light from everywhere adds up to 4π times its radiance
+    let white = EnvironmentMap::new(
+      width,
+      height,
+      vec![Color::new(1., 1., 1.); 16 * 8],
+      0.7,
+      2.,
+      256,
+    );
+    let total: f32 = white
+      .samples(&origin, &mut rng)
+      .iter()
+      .map(|s| s.color.r * s.intensity)
+      .sum();
+    assert!((total - 8. * PI).abs() < 8. * PI * 0.02, "{}", total);
+
+    // all the samples go towards the only bright pixel, seen in the same
+    // direction whatever the rotation
+    let mut pixels = vec![Color::zero(); 16 * 8];
+    pixels[2 * 16 + 5] = Color::new(4., 2., 1.);
+    for &rotation in &[0., 1., -2.5] {
+      let map = EnvironmentMap::new(width, height, pixels.clone(), rotation, 1., 16);
+      let samples = map.samples(&origin, &mut rng);
+      assert_eq!(samples.len(), 16);
+      for sample in samples {
+        assert_eq!(pixel_at(&map, map.image_point(&sample.direction)), (5, 2));
+      }
+      let center = map.direction((5.5 / 16., 2.5 / 8.));
+      let seen = map.background(&center).unwrap();
+      assert!((seen.r - 4.).abs() < 1e-3 && (seen.b - 1.).abs() < 1e-3);
+      for sample in map.samples(&origin, &mut rng) {
+        assert!(map.background(&sample.direction).unwrap().r > 0.);
+      }
+      let (u, v) = map.image_point(&map.direction((0.3, 0.6)));
+      assert!((u - 0.3).abs() < 1e-5 && (v - 0.6).abs() < 1e-5);
+    }
+    // the center of the image is in front of the unrotated camera
+    let front = EnvironmentMap::new(width, height, pixels, 0., 1., 16);
+    let (u, v) = front.image_point(&-Vector3::z_axis());
+    assert!((u - 0.5).abs() < 1e-5 && (v - 0.5).abs() < 1e-5);
+  }
+
+  #[test]
+  fn test_load() {
+    // brighter than white, which only linear formats can hold
+    let (width, height) = (4, 2);
+    let value = |x: usize, y: usize| (x as f32 * 2., y as f32, 0.5);
+    let directory = std::env::temp_dir();
+    let hdr = directory.join("rust_3d_test_environment.hdr");
+    let data: Vec<image::Rgb<f32>> = (0..width * height)
+      .map(|i| {
+        let (r, g, b) = value(i % width, i / width);
+        image::Rgb([r, g, b])
+      })
+      .collect();
+    image::codecs::hdr::HdrEncoder::new(File::create(&hdr).unwrap())
+      .encode(&data, width, height)
+      .unwrap();
+    let exr = directory.join("rust_3d_test_environment.exr");
+    exr::prelude::write_rgb_file(&exr, width, height, value).unwrap();
+
+    for path in &[hdr, exr] {
+      let map = EnvironmentMap::load(path, 0., 1., 1).unwrap();
+      assert_eq!((map.width, map.height), (4, 2));
+      let pixel = map.pixel(3, 1);
+      // .hdr stores 8 bits of mantissa
+      assert!((pixel.r - 6.).abs() < 0.05, "{:?} {:?}", path, pixel);
+      assert!((pixel.g - 1.).abs() < 0.05 && (pixel.b - 0.5).abs() < 0.05);
+      std::fs::remove_file(path).unwrap();
+    }
+    assert!(EnvironmentMap::load(Path::new("missing.hdr"), 0., 1., 1).is_err());
+  }
+}
@@ -0,0 +1,76 @@
+use crate::color::Color;
+use crate::display::DisplayTransform;
+use image::{ImageBuffer, Rgb};
+use std::path::Path;
+
+// in-memory image the renderer writes linear radiance into, so a frame can
+// be shown in the SDL window or saved to disk without one
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+  pub width: u32,
+  pub height: u32,
+  pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+  pub fn new(width: u32, height: u32) -> Framebuffer {
+    Framebuffer {
+      width,
+      height,
+      pixels: vec![Color::zero(); (width * height) as usize],
+    }
+  }
+
+  pub fn get(&self, x: u32, y: u32) -> Color {
+    self.pixels[(y * self.width + x) as usize]
+  }
+
+  // pixels in row-major order, each paired with its (x, y) coordinates
+  pub fn par_pixels_mut(
+    &mut self,
+  ) -> impl rayon::iter::IndexedParallelIterator<Item = ((u32, u32), &mut Color)> {
+    use rayon::prelude::*;
+    let width = self.width;
+    self
+      .pixels
+      .par_iter_mut()
+      .enumerate()
+      .map(move |(i, color)| ((i as u32 % width, i as u32 / width), color))
+  }
+
+  // the format is picked from the extension, e.g. "out.png" or "out.ppm"
+  pub fn save(&self, path: &Path, transform: &DisplayTransform) -> Result<(), String> {
+    let image = ImageBuffer::from_fn(self.width, self.height, |x, y| {
+      Rgb::<u8>::from(transform.apply(self.get(x, y)))
+    });
+    image
+      .save(path)
+      .map_err(|e| format!("could not write {}: {}", path.display(), e))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rayon::prelude::*;
+  use std::fs;
+
+  #[test]
+  fn test_save_ppm() {
+    let mut framebuffer = Framebuffer::new(2, 1);
+    framebuffer.par_pixels_mut().for_each(|((x, _), color)| {
+      if x == 1 {
+        *color = Color::from_srgb(1., 128. / 255., 0.);
+      }
+    });
+    let path = std::env::temp_dir().join("rust-3d-test_save_ppm.ppm");
+    framebuffer
+      .save(&path, &DisplayTransform::default())
+      .unwrap();
+    let bytes = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(bytes.starts_with(b"P6"));
+    assert_eq!(&bytes[bytes.len() - 6..], &[0, 0, 0, 255, 128, 0]);
+  }
+}
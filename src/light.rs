@@ -1,9 +1,854 @@
 use crate::color::Color;
 use crate::ray::Ray;
+use crate::sampling::{
+  concentric_sample_disk, cosine_sample_hemisphere, orthonormal_basis, uniform_sample_sphere,
+  SamplePattern,
+};
+use na::{Isometry3, Point3, Unit, Vector3};
+use rand::{Rng, RngCore};
+use std::f32::consts::PI;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+// Something lighting the scene. Surfaces are lit by samples of the light
+// reaching them, each tested for shadows with a ray towards the light.
+pub trait Light: Debug + Send + Sync {
+  // light arriving at `point` from as many points of the light as it takes
+  fn samples(&self, point: &Point3<f32>, rng: &mut dyn RngCore) -> Vec<LightSample>;
+
+  // distance along `ray` to the surface of the light and the light seen
+  // there, for lights that can be seen
+  fn cast_ray(&self, _ray: &Ray) -> Option<(f32, Color)> {
+    None
+  }
+
+  // light coming from infinitely far away along `direction`, for lights
+  // surrounding the scene, seen instead of the background
+  fn background(&self, _direction: &Unit<Vector3<f32>>) -> Option<Color> {
+    None
+  }
+
+  // whether rays can find the light, along `cast_ray`, as a `background` or
+  // as the emissive shape it is the light of: its reflections off smooth
+  // surfaces are then traced rather than sampled
+  fn is_seen(&self) -> bool {
+    false
+  }
+
+  // whether photons are traced from the light, see `emit_photon`
+  fn emits_photons(&self) -> bool {
+    false
+  }
+
+  // a ray leaving the light and the power of the whole light carried along
+  // it, for lights that emit photons; `bounds` is the center and the radius
+  // of a sphere around the shapes
+  fn emit_photon(
+    &self,
+    _bounds: &(Point3<f32>, f32),
+    _rng: &mut dyn RngCore,
+  ) -> Option<(Ray, Color)> {
+    None
+  }
+}
+
+// light reaching a point from one point of a light, before shadows
+#[derive(Debug, Copy, Clone)]
+pub struct LightSample {
+  // towards the light
+  pub direction: Unit<Vector3<f32>>,
+  // infinite for lights infinitely far away
+  pub distance: f32,
+  pub color: Color,
+  // of the light at the point hit, on a surface facing it
+  pub intensity: f32,
+  // whether photons are traced from this part of the light, its light
+  // going through glass then being found in the caustics rather than by
+  // shadow rays
+  pub in_caustics: bool,
+}
 
 #[derive(Debug, Copy, Clone)]
 pub struct PointLight {
-  pub ray: Ray,
+  pub position: Point3<f32>,
+  pub color: Color,
+  // emitted in every direction
+  pub intensity: f32,
+}
+
+impl Light for PointLight {
+  fn samples(&self, point: &Point3<f32>, _rng: &mut dyn RngCore) -> Vec<LightSample> {
+    let to_light = self.position - point;
+    vec![LightSample {
+      direction: Unit::new_normalize(to_light),
+      distance: to_light.norm(),
+      color: self.color,
+      intensity: self.intensity / (4. * PI * to_light.norm_squared()),
+      in_caustics: true,
+    }]
+  }
+
+  fn emits_photons(&self) -> bool {
+    true
+  }
+
+  fn emit_photon(
+    &self,
+    _bounds: &(Point3<f32>, f32),
+    rng: &mut dyn RngCore,
+  ) -> Option<(Ray, Color)> {
+    let ray = Ray {
+      origin: self.position,
+      direction: uniform_sample_sphere((rng.gen(), rng.gen())),
+    };
+    Some((ray, self.color * self.intensity))
+  }
+}
+
+// parallel rays, from a light infinitely far away like the sun
+#[derive(Debug, Copy, Clone)]
+pub struct DirectionalLight {
+  // in which the light goes
+  pub direction: Unit<Vector3<f32>>,
+  pub color: Color,
+  // on a surface facing the light
+  pub intensity: f32,
+}
+
+impl Light for DirectionalLight {
+  fn samples(&self, _point: &Point3<f32>, _rng: &mut dyn RngCore) -> Vec<LightSample> {
+    vec![LightSample {
+      direction: -self.direction,
+      distance: f32::INFINITY,
+      color: self.color,
+      intensity: self.intensity,
+      in_caustics: true,
+    }]
+  }
+
+  fn emits_photons(&self) -> bool {
+    true
+  }
+
+  // through a disk as wide as the scene, in front of it
+  fn emit_photon(
+    &self,
+    &(center, radius): &(Point3<f32>, f32),
+    rng: &mut dyn RngCore,
+  ) -> Option<(Ray, Color)> {
+    let (x, y) = concentric_sample_disk((rng.gen(), rng.gen()));
+    let (tangent, bitangent) = orthonormal_basis(&self.direction);
+    let ray = Ray {
+      origin: center + (tangent * x + bitangent * y - self.direction.into_inner()) * radius,
+      direction: self.direction,
+    };
+    Some((ray, self.color * (self.intensity * PI * radius * radius)))
+  }
+}
+
+// A point light only shining in a cone: fully inside `inner_angle` of its
+// direction, fading out up to `outer_angle`, `falloff` sharpening the fade
+// when above 1.
+#[derive(Debug, Copy, Clone)]
+pub struct SpotLight {
+  pub position: Point3<f32>,
+  pub direction: Unit<Vector3<f32>>,
+  // in radians, from the direction to the edge of the cone
+  pub inner_angle: f32,
+  pub outer_angle: f32,
+  pub falloff: f32,
+  pub color: Color,
+  // as much as a point light in the cone
+  pub intensity: f32,
+}
+
+impl SpotLight {
+  // 1 inside the inner cone, 0 outside of the outer one
+  fn cone_attenuation(&self, from_light: &Vector3<f32>) -> f32 {
+    let cos = self.direction.dot(from_light);
+    let (cos_inner, cos_outer) = (self.inner_angle.cos(), self.outer_angle.cos());
+    if cos >= cos_inner {
+      return 1.;
+    }
+    let t = ((cos - cos_outer) / (cos_inner - cos_outer).max(1e-6)).clamp(0., 1.);
+    t.powf(self.falloff)
+  }
+}
+
+impl Light for SpotLight {
+  fn samples(&self, point: &Point3<f32>, _rng: &mut dyn RngCore) -> Vec<LightSample> {
+    let to_light = self.position - point;
+    let direction = Unit::new_normalize(to_light);
+    let attenuation = self.cone_attenuation(&-direction.into_inner());
+    if attenuation <= 0. {
+      return vec![];
+    }
+    vec![LightSample {
+      direction,
+      distance: to_light.norm(),
+      color: self.color,
+      intensity: attenuation * self.intensity / (4. * PI * to_light.norm_squared()),
+      in_caustics: true,
+    }]
+  }
+
+  fn emits_photons(&self) -> bool {
+    true
+  }
+
+  // uniformly in the outer cone
+  fn emit_photon(
+    &self,
+    _bounds: &(Point3<f32>, f32),
+    rng: &mut dyn RngCore,
+  ) -> Option<(Ray, Color)> {
+    let cos_outer = self.outer_angle.cos();
+    let cos = 1. - rng.gen::<f32>() * (1. - cos_outer);
+    let sin = (1. - cos * cos).max(0.).sqrt();
+    let angle = 2. * PI * rng.gen::<f32>();
+    let (tangent, bitangent) = orthonormal_basis(&self.direction);
+    let direction = Unit::new_normalize(
+      (tangent * angle.cos() + bitangent * angle.sin()) * sin + self.direction.into_inner() * cos,
+    );
+    let solid_angle = 2. * PI * (1. - cos_outer);
+    let attenuation = self.cone_attenuation(&direction);
+    let ray = Ray {
+      origin: self.position,
+      direction,
+    };
+    Some((
+      ray,
+      self.color * (attenuation * self.intensity * solid_angle / (4. * PI)),
+    ))
+  }
+}
+
+// Light of the same color coming from every direction, like an overcast
+// sky, also seen by rays leaving the scene. Sampled uniformly over the
+// sphere, `samples` shadow rays each time.
+#[derive(Debug, Copy, Clone)]
+pub struct EnvironmentLight {
   pub color: Color,
   pub intensity: f32,
+  pub samples: u32,
+}
+
+impl Light for EnvironmentLight {
+  fn samples(&self, _point: &Point3<f32>, mut rng: &mut dyn RngCore) -> Vec<LightSample> {
+    let count = self.samples.max(1);
+    SamplePattern::Jittered
+      .generate(count, 0, 0, &mut rng)
+      .into_iter()
+      .map(|(x, y)| LightSample {
+        direction: uniform_sample_sphere((x, y)),
+        distance: f32::INFINITY,
+        color: self.color,
+        // radiance over the probability density of 1 / 4π
+        intensity: self.intensity * 4. * PI / count as f32,
+        in_caustics: false,
+      })
+      .collect()
+  }
+
+  fn background(&self, _direction: &Unit<Vector3<f32>>) -> Option<Color> {
+    Some(self.color * self.intensity)
+  }
+
+  fn is_seen(&self) -> bool {
+    true
+  }
+}
+
+#[derive(Debug, Clone)]
+pub enum AreaShape {
+  // spanning center ± u ± v, lighting towards u × v
+  Rectangle {
+    center: Point3<f32>,
+    u: Vector3<f32>,
+    v: Vector3<f32>,
+  },
+  // lighting towards `normal`
+  Disk {
+    center: Point3<f32>,
+    normal: Unit<Vector3<f32>>,
+    radius: f32,
+  },
+  Sphere {
+    center: Point3<f32>,
+    radius: f32,
+  },
+  // the surfaces of emissive shapes, lighting towards their outside
+  Triangles(Arc<TriangleSet>),
+  // sides and caps of a cylinder around the y axis of `to_world`
+  Cylinder {
+    to_world: Isometry3<f32>,
+    radius: f32,
+    half_height: f32,
+  },
+  // side and base of a cone based on the origin of `to_world`, its apex
+  // `height` up its y axis
+  Cone {
+    to_world: Isometry3<f32>,
+    radius: f32,
+    height: f32,
+  },
+  // ring around the y axis of `to_world`
+  Torus {
+    to_world: Isometry3<f32>,
+    major_radius: f32,
+    minor_radius: f32,
+  },
+}
+
+// triangles lighting the side their vertices go counter-clockwise around,
+// picked according to their areas
+#[derive(Debug)]
+pub struct TriangleSet {
+  triangles: Vec<[Point3<f32>; 3]>,
+  // of the triangles up to each one, included
+  cumulative_areas: Vec<f32>,
+}
+
+impl TriangleSet {
+  // None when they have no area
+  pub fn new(triangles: Vec<[Point3<f32>; 3]>) -> Option<TriangleSet> {
+    let mut total = 0.;
+    let cumulative_areas: Vec<f32> = triangles
+      .iter()
+      .map(|[a, b, c]| {
+        total += (b - a).cross(&(c - a)).norm() / 2.;
+        total
+      })
+      .collect();
+    if total > 0. {
+      Some(TriangleSet {
+        triangles,
+        cumulative_areas,
+      })
+    } else {
+      None
+    }
+  }
+
+  fn area(&self) -> f32 {
+    self.cumulative_areas.last().cloned().unwrap_or(0.)
+  }
+
+  // uniform over the triangles, `x` picking one then the point on it
+  fn surface_point(&self, (x, y): (f32, f32)) -> (Point3<f32>, Unit<Vector3<f32>>) {
+    let target = x * self.area();
+    let index = self
+      .cumulative_areas
+      .partition_point(|&area| area <= target)
+      .min(self.triangles.len() - 1);
+    let before = if index > 0 {
+      self.cumulative_areas[index - 1]
+    } else {
+      0.
+    };
+    let x = ((target - before) / (self.cumulative_areas[index] - before)).clamp(0., 1.);
+    let [a, b, c] = self.triangles[index];
+    // https://pbr-book.org/3ed-2018/Monte_Carlo_Integration/2D_Sampling_with_Multidimensional_Transformations#SamplingaTriangle
+    let root = x.sqrt();
+    let point = a + (b - a) * (root * (1. - y)) + (c - a) * (root * y);
+    (point, Unit::new_normalize((b - a).cross(&(c - a))))
+  }
+}
+
+// A light with a surface, whose shadows have penumbrae: every point of the
+// surface glows with the same radiance, and `samples` of them are tested
+// for shadows at each point lit.
+#[derive(Debug, Clone)]
+pub struct AreaLight {
+  pub shape: AreaShape,
+  pub color: Color,
+  // emitted by the whole surface, like a point light's
+  pub intensity: f32,
+  pub samples: u32,
+}
+
+// points of an emissive shape tested for shadows at each point lit
+const EMITTER_SAMPLES: u32 = 16;
+
+impl AreaLight {
+  // of a surface glowing with `radiance`
+  pub fn emitting(shape: AreaShape, radiance: Color) -> AreaLight {
+    let mut light = AreaLight {
+      shape,
+      color: radiance,
+      intensity: 0.,
+      samples: EMITTER_SAMPLES,
+    };
+    light.intensity = PI * light.area();
+    light
+  }
+
+  fn area(&self) -> f32 {
+    match self.shape {
+      AreaShape::Rectangle { u, v, .. } => 4. * u.cross(&v).norm(),
+      AreaShape::Disk { radius, .. } => PI * radius * radius,
+      AreaShape::Sphere { radius, .. } => 4. * PI * radius * radius,
+      AreaShape::Triangles(ref triangles) => triangles.area(),
+      AreaShape::Cylinder {
+        radius,
+        half_height,
+        ..
+      } => 2. * PI * radius * (2. * half_height + radius),
+      AreaShape::Cone { radius, height, .. } => {
+        PI * radius * (radius + (radius * radius + height * height).sqrt())
+      }
+      AreaShape::Torus {
+        major_radius,
+        minor_radius,
+        ..
+      } => 4. * PI * PI * major_radius * minor_radius,
+    }
+  }
+
+  // of its surface, towards its lit side
+  pub fn radiance(&self) -> Color {
+    self.color * (self.intensity / (PI * self.area()))
+  }
+
+  // a point of the surface and its normal for a point of the unit square;
+  // for spheres, on the half facing `towards`
+  fn surface_point(
+    &self,
+    (x, y): (f32, f32),
+    towards: &Point3<f32>,
+  ) -> (Point3<f32>, Unit<Vector3<f32>>) {
+    match self.shape {
+      AreaShape::Rectangle { center, u, v } => (
+        center + u * (2. * x - 1.) + v * (2. * y - 1.),
+        Unit::new_normalize(u.cross(&v)),
+      ),
+      AreaShape::Disk {
+        center,
+        normal,
+        radius,
+      } => {
+        let (dx, dy) = concentric_sample_disk((x, y));
+        let (tangent, bitangent) = orthonormal_basis(&normal);
+        (center + (tangent * dx + bitangent * dy) * radius, normal)
+      }
+      AreaShape::Sphere { center, radius } => {
+        let mut normal = uniform_sample_sphere((x, y));
+        if normal.dot(&(towards - center)) < 0. {
+          normal = -normal;
+        }
+        (center + normal.into_inner() * radius, normal)
+      }
+      AreaShape::Triangles(ref triangles) => triangles.surface_point((x, y)),
+      AreaShape::Cylinder {
+        to_world,
+        radius,
+        half_height,
+      } => {
+        // the side or one of the caps, according to their areas
+        let side = 2. * half_height / (2. * half_height + radius);
+        let (point, normal) = if x < side {
+          let angle = 2. * PI * x / side;
+          let around = Vector3::new(angle.sin(), 0., angle.cos());
+          (
+            Point3::from(around * radius) + Vector3::y() * (half_height * (2. * y - 1.)),
+            around,
+          )
+        } else {
+          let x = ((x - side) / (1. - side) * 2.).min(2. - 1e-6);
+          let (up, x) = if x < 1. { (1., x) } else { (-1., x - 1.) };
+          let (dx, dz) = concentric_sample_disk((x, y));
+          (
+            Point3::new(dx * radius, up * half_height, dz * radius),
+            Vector3::y() * up,
+          )
+        };
+        (
+          to_world.transform_point(&point),
+          Unit::new_normalize(to_world.transform_vector(&normal)),
+        )
+      }
+      AreaShape::Cone {
+        to_world,
+        radius,
+        height,
+      } => {
+        let slant = (radius * radius + height * height).sqrt();
+        let side = slant / (slant + radius);
+        let (point, normal) = if x < side {
+          // as much further from the apex as there is more of the side there
+          let angle = 2. * PI * x / side;
+          let from_apex = y.sqrt();
+          let (sin, cos) = angle.sin_cos();
+          (
+            Point3::new(
+              sin * radius * from_apex,
+              height * (1. - from_apex),
+              cos * radius * from_apex,
+            ),
+            Vector3::new(sin * height, radius, cos * height),
+          )
+        } else {
+          let (dx, dz) = concentric_sample_disk(((x - side) / (1. - side), y));
+          (Point3::new(dx * radius, 0., dz * radius), -Vector3::y())
+        };
+        (
+          to_world.transform_point(&point),
+          Unit::new_normalize(to_world.transform_vector(&normal)),
+        )
+      }
+      AreaShape::Torus {
+        to_world,
+        major_radius,
+        minor_radius,
+      } => {
+        // around the ring, then around the tube from its outer equator,
+        // where there is more of the surface, by inverting R θ + r sin θ
+        let angle = 2. * PI * x;
+        let around = Vector3::new(angle.sin(), 0., angle.cos());
+        let target = 2. * PI * major_radius * y;
+        let mut tube = 2. * PI * y;
+        for _ in 0..4 {
+          tube -= (major_radius * tube + minor_radius * tube.sin() - target)
+            / (major_radius + minor_radius * tube.cos());
+        }
+        let normal = around * tube.cos() + Vector3::y() * tube.sin();
+        let point = Point3::from(around * major_radius + normal * minor_radius);
+        (
+          to_world.transform_point(&point),
+          Unit::new_normalize(to_world.transform_vector(&normal)),
+        )
+      }
+    }
+  }
+}
+
+impl Light for AreaLight {
+  // stratified over the surface, each carrying its share of the light
+  fn samples(&self, point: &Point3<f32>, mut rng: &mut dyn RngCore) -> Vec<LightSample> {
+    let count = self.samples.max(1);
+    // only half of a sphere is sampled
+    let sampled_area = match self.shape {
+      AreaShape::Sphere { .. } => self.area() / 2.,
+      _ => self.area(),
+    };
+    let radiance = self.intensity / (PI * self.area());
+    SamplePattern::Jittered
+      .generate(count, 0, 0, &mut rng)
+      .into_iter()
+      .filter_map(|square_point| {
+        let (light_point, normal) = self.surface_point(square_point, point);
+        let to_light = light_point - point;
+        let direction = Unit::new_normalize(to_light);
+        let cos_light = -normal.dot(&direction);
+        if cos_light <= 0. {
+          return None;
+        }
+        Some(LightSample {
+          direction,
+          distance: to_light.norm(),
+          color: self.color,
+          intensity: radiance * cos_light * sampled_area / (to_light.norm_squared() * count as f32),
+          in_caustics: true,
+        })
+      })
+      .collect()
+  }
+
+  // distance along `ray` to the surface and the light seen there, none from
+  // behind rectangles and disks
+  fn cast_ray(&self, ray: &Ray) -> Option<(f32, Color)> {
+    let (distance, normal) = match self.shape {
+      AreaShape::Rectangle { center, u, v } => {
+        let normal = u.cross(&v);
+        let distance = plane_distance(ray, &center, &normal)?;
+        let offset = ray.origin + ray.direction.into_inner() * distance - center;
+        if offset.dot(&u).abs() > u.norm_squared() || offset.dot(&v).abs() > v.norm_squared() {
+          return None;
+        }
+        (distance, normal)
+      }
+      AreaShape::Disk {
+        center,
+        normal,
+        radius,
+      } => {
+        let distance = plane_distance(ray, &center, &normal)?;
+        let offset = ray.origin + ray.direction.into_inner() * distance - center;
+        if offset.norm_squared() > radius * radius {
+          return None;
+        }
+        (distance, normal.into_inner())
+      }
+      AreaShape::Sphere { center, radius } => {
+        let to_center = center - ray.origin;
+        let projection = to_center.dot(&ray.direction);
+        let squared_miss = to_center.norm_squared() - projection * projection;
+        if squared_miss > radius * radius {
+          return None;
+        }
+        let half_chord = (radius * radius - squared_miss).sqrt();
+        let distance = if projection - half_chord > 0. {
+          projection - half_chord
+        } else {
+          projection + half_chord
+        };
+        if distance <= 0. {
+          return None;
+        }
+        (distance, -ray.direction.into_inner())
+      }
+      // the surfaces of emissive shapes, which rays see as the shapes
+      AreaShape::Triangles(_)
+      | AreaShape::Cylinder { .. }
+      | AreaShape::Cone { .. }
+      | AreaShape::Torus { .. } => return None,
+    };
+    let color = if normal.dot(&ray.direction) < 0. {
+      self.radiance()
+    } else {
+      Color::zero()
+    };
+    Some((distance, color))
+  }
+
+  fn is_seen(&self) -> bool {
+    true
+  }
+
+  fn emits_photons(&self) -> bool {
+    true
+  }
+
+  // from a uniform point of the surface, cosine weighted around its normal
+  fn emit_photon(
+    &self,
+    _bounds: &(Point3<f32>, f32),
+    mut rng: &mut dyn RngCore,
+  ) -> Option<(Ray, Color)> {
+    let square_point = (rng.gen(), rng.gen());
+    let (point, normal) = match self.shape {
+      AreaShape::Sphere { center, radius } => {
+        let normal = uniform_sample_sphere(square_point);
+        (center + normal.into_inner() * radius, normal)
+      }
+      _ => self.surface_point(square_point, &Point3::origin()),
+    };
+    let ray = Ray {
+      origin: point + normal.into_inner() * 0.001,
+      direction: cosine_sample_hemisphere(&normal, &mut rng),
+    };
+    Some((ray, self.color * self.intensity))
+  }
+}
+
+// The light of an emissive shape, lighting the scene like an area light of
+// the same surface. Rays already see the shape itself, so not the light.
+#[derive(Debug, Clone)]
+pub struct Emitter(pub AreaLight);
+
+impl Light for Emitter {
+  fn samples(&self, point: &Point3<f32>, rng: &mut dyn RngCore) -> Vec<LightSample> {
+    self.0.samples(point, rng)
+  }
+
+  fn is_seen(&self) -> bool {
+    true
+  }
+
+  fn emits_photons(&self) -> bool {
+    true
+  }
+
+  fn emit_photon(
+    &self,
+    bounds: &(Point3<f32>, f32),
+    rng: &mut dyn RngCore,
+  ) -> Option<(Ray, Color)> {
+    self.0.emit_photon(bounds, rng)
+  }
+}
+
+fn plane_distance(ray: &Ray, center: &Point3<f32>, normal: &Vector3<f32>) -> Option<f32> {
+  let denominator = normal.dot(&ray.direction);
+  if denominator.abs() < 1e-6 {
+    return None;
+  }
+  let distance = normal.dot(&(center - ray.origin)) / denominator;
+  if distance > 0. {
+    Some(distance)
+  } else {
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+
+  #[test]
+  fn test_area_light() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let shapes = [
+      AreaShape::Rectangle {
+        center: Point3::origin(),
+        u: Vector3::new(0.5, 0., 0.),
+        v: Vector3::new(0., 0., -0.5),
+      },
+      AreaShape::Disk {
+        center: Point3::origin(),
+        normal: Vector3::y_axis(),
+        radius: 0.5,
+      },
+      AreaShape::Sphere {
+        center: Point3::origin(),
+        radius: 0.5,
+      },
+    ];
+    let far = Point3::new(0., 100., 0.);
+    for shape in &shapes {
+      let light = AreaLight {
+        shape: shape.clone(),
+        color: Color::new(1., 1., 1.),
+        intensity: 1000.,
+        samples: 64,
+      };
+      // from afar, flat lights give intensity / (π d²) straight ahead and
+      // spheres as much as a point light
+      let total: f32 = light
+        .samples(&far, &mut rng)
+        .iter()
+        .map(|s| s.intensity)
+        .sum();
+      let expected = light.intensity / (PI * 100. * 100.);
+      let expected = match shape {
+        // a sphere lights every side
+        AreaShape::Sphere { .. } => expected / 4.,
+        _ => expected,
+      };
+      assert!(
+        (total - expected).abs() < expected * 0.05,
+        "{:?} {}",
+        shape,
+        total
+      );
+
+      // seen from above, and not from below unless it is a sphere
+      let down = Ray {
+        origin: far,
+        direction: -Vector3::y_axis(),
+      };
+      let (distance, color) = light.cast_ray(&down).unwrap();
+      assert!(
+        (distance
+          - if let AreaShape::Sphere { .. } = shape {
+            99.5
+          } else {
+            100.
+          })
+        .abs()
+          < 1e-3
+      );
+      assert!(color.r > 0.);
+      let up = Ray {
+        origin: Point3::new(0., -100., 0.),
+        direction: Vector3::y_axis(),
+      };
+      let (_, color) = light.cast_ray(&up).unwrap();
+      assert_eq!(color.r > 0., matches!(shape, AreaShape::Sphere { .. }));
+    }
+  }
+
+  #[test]
+  fn test_spot_and_directional_lights() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let spot = SpotLight {
+      position: Point3::new(0., 10., 0.),
+      direction: -Vector3::y_axis(),
+      inner_angle: 0.2,
+      outer_angle: 0.4,
+      falloff: 1.,
+      color: Color::new(1., 1., 1.),
+      intensity: 1000.,
+    };
+    let at = |x: f32, rng: &mut StdRng| {
+      spot
+        .samples(&Point3::new(x, 0., 0.), rng)
+        .first()
+        .map_or(0., |s| s.intensity)
+    };
+    // as a point light in the inner cone, fading out up to the outer one
+    assert!((at(0., &mut rng) - 1000. / (4. * PI * 100.)).abs() < 1e-4);
+    let (inner, middle, outer) = (
+      at(10. * 0.19f32.tan(), &mut rng),
+      at(10. * 0.3f32.tan(), &mut rng),
+      at(10. * 0.41f32.tan(), &mut rng),
+    );
+    assert!(inner > middle && middle > 0.);
+    assert_eq!(outer, 0.);
+
+    let sun = DirectionalLight {
+      direction: Unit::new_normalize(Vector3::new(1., -1., 0.)),
+      color: Color::new(1., 1., 1.),
+      intensity: 2.,
+    };
+    for point in &[Point3::origin(), Point3::new(100., -3., 7.)] {
+      let sample = sun.samples(point, &mut rng)[0];
+      assert_eq!(sample.intensity, 2.);
+      assert!(sample.distance.is_infinite());
+      assert!(
+        (sample.direction.into_inner() - Vector3::new(-1., 1., 0.).normalize()).norm() < 1e-6
+      );
+    }
+  }
+
+  #[test]
+  fn test_emitter() {
+    use crate::material::Material;
+    use crate::shapes::test_helpers::material;
+    use crate::shapes::{plane::Plane, sphere::Sphere, Castable};
+
+    let material = |emission: Color| Material {
+      emission,
+      ..material()
+    };
+    let glow = Color::new(2., 1., 0.5);
+    let tilted = Unit::new_normalize(Vector3::new(0.3, -1., 0.2));
+    let panel = |size| {
+      Plane::new(
+        tilted,
+        Point3::new(0., 3., 0.),
+        size,
+        na::zero(),
+        material(glow),
+      )
+    };
+
+    // a bounded plane glows towards its normal with its material's emission
+    let light = panel((Some(1.), Some(2.))).emitters().remove(0);
+    assert!((light.radiance().r - glow.r).abs() < 1e-4);
+    match light.shape {
+      AreaShape::Rectangle { u, v, .. } => {
+        assert!(u.cross(&v).normalize().dot(&tilted) > 0.999)
+      }
+      _ => panic!("a plane should be a rectangle"),
+    }
+    let mut rng = StdRng::seed_from_u64(0);
+    assert!(!Emitter(light)
+      .samples(&Point3::origin(), &mut rng)
+      .is_empty());
+
+    // infinite planes and surfaces that don't glow aren't lights
+    assert!(panel((Some(1.), None)).emitters().is_empty());
+    assert!(Sphere::new(Point3::origin(), 1., material(Color::zero()))
+      .emitters()
+      .is_empty());
+    assert_eq!(
+      Sphere::new(Point3::origin(), 1., material(glow))
+        .emitters()
+        .len(),
+      1
+    );
+  }
 }
@@ -1,108 +1,155 @@
 extern crate nalgebra as na;
 extern crate sdl2;
 
-use crate::color::Color;
-use crate::shapes::plane::Plane;
-use crate::shapes::Shape;
+use crate::display::{DisplayTransform, ToneMapping, TransferFunction};
+use crate::framebuffer::Framebuffer;
 use core::f32::consts::FRAC_PI_2;
 use core::f32::consts::PI;
-use light::PointLight;
-use material::{Material, MaterialType};
 use na::geometry::Rotation3;
-use na::{Isometry3, Perspective3, Point2, Point3};
+use na::{Isometry3, Point3};
 use na::{Unit, Vector3};
 use nalgebra::{Quaternion, UnitQuaternion};
+use rand::Rng;
 use rayon::prelude::*;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Instant;
 
-const SCREEN_WIDTH: f32 = 400.0;
-const SCREEN_HEIGHT: f32 = 300.0;
+const DEFAULT_RESOLUTION: Resolution = Resolution {
+  width: 400,
+  height: 300,
+};
 const SCALE: f32 = 2.;
 
+mod aabb;
+mod brdf;
+mod bvh;
+mod camera;
 mod color;
+mod display;
+mod environment;
+mod framebuffer;
 mod light;
 mod material;
+mod noise;
+mod obj;
 mod ops;
+mod photon_map;
 mod ray;
+mod sampling;
+mod scene_file;
 mod shapes;
+mod sky;
+mod texture;
+mod volume;
 mod world;
-use ray::Ray;
-use shapes::sphere::Sphere;
-use world::World;
-
-type ScreenPoint = Point2<f32>;
-struct NDCCoords {
-  near: Point3<f32>,
-  // far: Point3<f32>,
-}
-
-impl From<ScreenPoint> for NDCCoords {
-  fn from(p: ScreenPoint) -> Self {
-    // Compute two points in clip-space.
-    // "ndc" = normalized device coordinates.
-    let near_ndc_point = Point3::new(p.x / SCREEN_WIDTH - 0.5, -(p.y / SCREEN_HEIGHT - 0.5), -1.0);
-    // let far_ndc_point = Point3::new(p.x / SCREEN_WIDTH - 0.5, -(p.y / SCREEN_HEIGHT - 0.5), 1.0);
+use camera::{Camera, Resolution};
+use sampling::{Filter, PixelSampler, SamplePattern};
+use scene_file::CameraDescription;
+use world::{Integrator, World};
 
-    NDCCoords {
-      near: near_ndc_point,
-      // far: far_ndc_point,
-    }
-  }
-}
-impl From<NDCCoords> for ScreenPoint {
-  fn from(p: NDCCoords) -> Self {
-    Point2::new(p.near.x * SCREEN_WIDTH, p.near.y * SCREEN_HEIGHT)
-  }
-}
-
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 struct Scene<'a> {
-  pub projection: Perspective3<f32>,
+  pub camera: Arc<dyn Camera + Send + Sync>,
+  pub resolution: Resolution,
   pub world: &'a World<'a>,
   pub eye: Point3<f32>,
   pub target: Point3<f32>,
   pub up: Vector3<f32>,
   pub theta_x: f32,
   pub theta_y: f32,
+  pub integrator: Integrator,
+  pub sampler: PixelSampler,
+}
+
+impl<'a> Scene<'a> {
+  fn camera_to_world(&self) -> Isometry3<f32> {
+    let view = UnitQuaternion::from_euler_angles(self.theta_x, self.theta_y, 0.).inverse()
+      * Isometry3::look_at_rh(&self.eye, &self.target, &self.up);
+    view.inverse()
+  }
+
+  // whether frames rendered from both can be averaged together
+  fn same_view(&self, other: &Scene) -> bool {
+    Arc::ptr_eq(&self.camera, &other.camera)
+      && self.eye == other.eye
+      && self.target == other.target
+      && self.theta_x == other.theta_x
+      && self.theta_y == other.theta_y
+      && self.integrator == other.integrator
+      && self.sampler == other.sampler
+  }
 }
 
+// light coming through a point of the screen, in pixels
 fn render(
-  (x, y): (i32, i32),
-  Scene {
-    projection,
-    world,
-    eye,
-    target,
-    up,
-    theta_x,
-    theta_y,
-  }: &Scene,
+  point: (f32, f32),
+  lens: (f32, f32),
+  scene: &Scene,
+  camera_to_world: &Isometry3<f32>,
+  rng: &mut impl Rng,
 ) -> color::Color {
-  let view = &UnitQuaternion::from_euler_angles(*theta_x, *theta_y, 0.).inverse()
-    * Isometry3::look_at_rh(&eye, &target, &up);
-  let screen_point = Point2::new(x as f32, y as f32);
-
-  let ndc: NDCCoords = screen_point.into();
-  // Unproject them to view-space.
-  let world_point = projection.unproject_point(&ndc.near);
-
-  let camera_point = view.inverse_transform_point(&world_point);
-
-  world.get_color_at_ray(
-    &Ray {
-      direction: Unit::new_normalize(camera_point - eye),
-      origin: *eye,
-    },
-    0,
-  )
+  match scene
+    .camera
+    .generate_ray(camera_to_world, point, lens, &scene.resolution)
+  {
+    Some(ray) => scene.world.get_color(&ray, scene.integrator, rng),
+    // outside of the lens
+    None => color::Color::zero(),
+  }
+}
+
+// filtered average of the samples around the pixel center
+fn render_pixel(
+  (x, y): (u32, u32),
+  scene: &Scene,
+  camera_to_world: &Isometry3<f32>,
+  pass: u32,
+) -> color::Color {
+  let mut rng = rand::thread_rng();
+  let pixel = y * scene.resolution.width + x;
+  let mut sum = color::Color::zero();
+  let mut total_weight = 0.;
+  let mut unweighted_sum = color::Color::zero();
+  let offsets = scene.sampler.offsets(pass, pixel, &mut rng);
+  for ((dx, dy), weight) in &offsets {
+    let color = render(
+      (x as f32 + dx, y as f32 + dy),
+      (rng.gen(), rng.gen()),
+      scene,
+      camera_to_world,
+      &mut rng,
+    );
+    sum += color * *weight;
+    total_weight += weight;
+    unweighted_sum += color;
+  }
+  if total_weight > 1e-3 {
+    sum * (1. / total_weight)
+  } else {
+    // few samples, all in the negative lobes of the filter
+    unweighted_sum * (1. / offsets.len() as f32)
+  }
+}
+
+// renders the `pass`-th frame of a progressive render (starting at 1),
+// averaging it into the previous ones
+fn render_frame(scene: &Scene, framebuffer: &mut Framebuffer, pass: u32) {
+  let weight = 1. / pass as f32;
+  let camera_to_world = scene.camera_to_world();
+  framebuffer.par_pixels_mut().for_each(|((x, y), color)| {
+    *color =
+      *color * (1. - weight) + render_pixel((x, y), scene, &camera_to_world, pass - 1) * weight
+  });
 }
 
 fn move_camera(scene: Scene, translation: Vector3<f32>) -> Scene {
-  let mut next_scene = scene;
   let rotated_translation = &UnitQuaternion::from_euler_angles(scene.theta_x, scene.theta_y, 0.)
     .transform_vector(&translation);
+  let mut next_scene = scene;
   next_scene.eye += rotated_translation;
   next_scene.target += rotated_translation;
   next_scene
@@ -119,6 +166,30 @@ fn rotate_camera(scene: Scene, direction: Vector3<f32>, radians: f32) -> Scene {
   next_scene
 }
 
+// puts the plane of focus on whatever is seen through a point of the window
+fn focus_camera(scene: Scene, (x, y): (i32, i32)) -> Scene {
+  let point = (x as f32 / SCALE, y as f32 / SCALE);
+  let lens_center = (0.5, 0.5);
+  // the plane of focus is perpendicular to the view direction: its distance
+  // is the depth of the hit rather than its distance along the ray
+  let depth = scene
+    .camera
+    .generate_camera_ray(point, lens_center, &scene.resolution)
+    .and_then(|ray| {
+      let info = scene
+        .world
+        .cast_to_shapes(&ray.apply_isometry(scene.camera_to_world()))?;
+      Some(info.distance * -ray.direction.z)
+    });
+  match depth.and_then(|depth| Some((depth, scene.camera.focused_at(depth)?))) {
+    Some((depth, camera)) => {
+      println!("focus distance: {}", depth);
+      Scene { camera, ..scene }
+    }
+    None => scene,
+  }
+}
+
 const MOVE_DELTA: f32 = 0.5;
 const ROTATION_DELTA: f32 = 10.0f32 * (PI / 180.0f32);
 
@@ -147,17 +218,39 @@ fn handle_input<'a>(
         X => return Some(rotate_camera(scene, Vector3::y(), -ROTATION_DELTA)),
         C => return Some(rotate_camera(scene, Vector3::x(), ROTATION_DELTA)),
         V => return Some(rotate_camera(scene, Vector3::x(), -ROTATION_DELTA)),
+
+        P => {
+          let integrator = match scene.integrator {
+            Integrator::Whitted => Integrator::from_name("path").unwrap(),
+            Integrator::PathTracing { .. } => Integrator::Whitted,
+          };
+          println!("integrator: {:?}", integrator);
+          return Some(Scene {
+            integrator,
+            ..scene
+          });
+        }
         _ => {}
       };
     }
     Event::MouseWheel { y, .. } => {
       return Some(move_camera(scene, Vector3::new(0., 0., -y as f32)))
     }
+    Event::MouseButtonDown {
+      mouse_btn: MouseButton::Right,
+      x,
+      y,
+      ..
+    } => return Some(focus_camera(scene, (x, y))),
     Event::MouseButtonDown { .. } => *mouse_clicked = true,
     Event::MouseButtonUp { .. } => *mouse_clicked = false,
     Event::MouseMotion { xrel, yrel, .. } if *mouse_clicked => {
-      let y_rotation = Vector3::y() * (-xrel as f32) * (PI / (SCREEN_WIDTH * SCALE));
-      let x_rotation = Vector3::x() * (-yrel as f32) * (PI / (SCREEN_HEIGHT * SCALE));
+      let (width, height) = (
+        scene.resolution.width as f32 * SCALE,
+        scene.resolution.height as f32 * SCALE,
+      );
+      let y_rotation = Vector3::y() * (-xrel as f32) * (PI / width);
+      let x_rotation = Vector3::x() * (-yrel as f32) * (PI / height);
       let axis = Unit::new_normalize(x_rotation + y_rotation);
       return Some(rotate_camera(
         scene,
@@ -170,46 +263,204 @@ fn handle_input<'a>(
   Some(scene)
 }
 
+fn initial_scene<'a>(
+  world: &'a World<'a>,
+  description: &CameraDescription,
+  camera: Arc<dyn Camera + Send + Sync>,
+  options: &Options,
+) -> Scene<'a> {
+  Scene {
+    camera,
+    resolution: options.resolution,
+    eye: scene_file::point(description.eye),
+    target: scene_file::point(description.target),
+    up: scene_file::vector(description.up),
+    world,
+    theta_x: 0.,
+    theta_y: 0.,
+    integrator: options.integrator,
+    sampler: options.sampler,
+  }
+}
+
 fn get_next_scene<'a>(
-  last_scene: Option<Scene<'a>>,
-  world: &'a world::World,
+  scene: Scene<'a>,
   event_pump: &mut sdl2::EventPump,
   mut mouse_clicked: &mut bool,
 ) -> Option<Scene<'a>> {
-  match last_scene {
-    None => {
-      let eye = Point3::new(0.0, 1.0, 0.0);
-      let target = Point3::new(0.0, 1.0, -1.0);
-      Some(Scene {
-        // A perspective projection.
-        projection: Perspective3::new(SCREEN_WIDTH / SCREEN_HEIGHT, 3.14 / 2.0, 1.0, 1000.0),
-        // view: Isometry3::look_at_rh(&eye, &target, &Vector3::y()),
-        eye,
-        target,
-        up: Vector3::y(),
-        world,
-        theta_x: 0.,
-        theta_y: 0.,
-      })
+  event_pump
+    .poll_iter()
+    .fold(Some(scene), |last_scene, event| match last_scene {
+      None => None,
+      Some(scene) => handle_input(scene, &mut mouse_clicked, event),
+    })
+}
+
+// a file the render is written to, with its own transfer function when one
+// was given after it
+struct Output {
+  path: PathBuf,
+  transfer: Option<TransferFunction>,
+}
+
+struct Options {
+  scene: PathBuf,
+  // when non-empty, render a single frame to these files instead of opening a window
+  outputs: Vec<Output>,
+  // of the window, and of the outputs without a transfer function of their own
+  display: DisplayTransform,
+  integrator: Integrator,
+  sampler: PixelSampler,
+  resolution: Resolution,
+  // frames averaged together when rendering to files
+  passes: u32,
+  // traced from the lights for caustics, none when 0, and the distance
+  // within which they are gathered
+  photons: u32,
+  photon_radius: f32,
+}
+
+const USAGE: &str = "usage: rust-3d [--scene <file.ron>] [--transfer srgb|linear|<gamma>]
+               [--output <file.png|file.ppm> [--transfer srgb|linear|<gamma>]]...
+               [--tonemap clamp|reinhard|aces] [--exposure <stops>]
+               [--integrator whitted|path] [--passes <count>]
+               [--photons <count>] [--photon-radius <distance>]
+               [--samples <count>] [--pattern regular|jittered|halton|sobol]
+               [--filter box|tent|gaussian|mitchell]
+               [--width <pixels>] [--height <pixels>]";
+const DEFAULT_SCENE: &str = "scenes/default.ron";
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+  let mut options = Options {
+    scene: PathBuf::from(DEFAULT_SCENE),
+    outputs: vec![],
+    display: DisplayTransform::default(),
+    integrator: Integrator::Whitted,
+    sampler: PixelSampler::default(),
+    resolution: DEFAULT_RESOLUTION,
+    passes: 1,
+    photons: 0,
+    photon_radius: 0.05,
+  };
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "-s" | "--scene" => match args.next() {
+        Some(path) => options.scene = PathBuf::from(path),
+        None => return Err(format!("missing file after {}\n{}", arg, USAGE)),
+      },
+      "-o" | "--output" => match args.next() {
+        Some(path) => options.outputs.push(Output {
+          path: PathBuf::from(path),
+          transfer: None,
+        }),
+        None => return Err(format!("missing file after {}\n{}", arg, USAGE)),
+      },
+      "--tonemap" => match args.next().as_deref().and_then(ToneMapping::from_name) {
+        Some(tone_mapping) => options.display.tone_mapping = tone_mapping,
+        None => {
+          return Err(format!(
+            "{} expects clamp, reinhard or aces\n{}",
+            arg, USAGE
+          ))
+        }
+      },
+      "--exposure" => match args.next().and_then(|stops| stops.parse().ok()) {
+        Some(exposure) => options.display.exposure = exposure,
+        None => return Err(format!("{} expects a number of stops\n{}", arg, USAGE)),
+      },
+      "--transfer" => match args.next().as_deref().and_then(TransferFunction::from_name) {
+        // for the output it follows, if any
+        Some(transfer) => match options.outputs.last_mut() {
+          Some(output) => output.transfer = Some(transfer),
+          None => options.display.transfer = transfer,
+        },
+        None => {
+          return Err(format!(
+            "{} expects srgb, linear or a gamma value\n{}",
+            arg, USAGE
+          ))
+        }
+      },
+      "--integrator" => match args.next().as_deref().and_then(Integrator::from_name) {
+        Some(integrator) => options.integrator = integrator,
+        None => return Err(format!("{} expects whitted or path\n{}", arg, USAGE)),
+      },
+      "--samples" => match args.next().and_then(|samples| samples.parse().ok()) {
+        Some(samples) if samples > 0 => options.sampler.samples = samples,
+        _ => return Err(format!("{} expects a positive count\n{}", arg, USAGE)),
+      },
+      "--pattern" => match args.next().as_deref().and_then(SamplePattern::from_name) {
+        Some(pattern) => options.sampler.pattern = pattern,
+        None => return Err(format!("{} expects a sample pattern\n{}", arg, USAGE)),
+      },
+      "--filter" => match args.next().as_deref().and_then(Filter::from_name) {
+        Some(filter) => options.sampler.filter = filter,
+        None => return Err(format!("{} expects a filter\n{}", arg, USAGE)),
+      },
+      "--width" | "--height" => match args.next().and_then(|pixels| pixels.parse().ok()) {
+        Some(pixels) if pixels > 0 => match arg.as_str() {
+          "--width" => options.resolution.width = pixels,
+          _ => options.resolution.height = pixels,
+        },
+        _ => return Err(format!("{} expects a positive size\n{}", arg, USAGE)),
+      },
+      "--passes" => match args.next().and_then(|passes| passes.parse().ok()) {
+        Some(passes) if passes > 0 => options.passes = passes,
+        _ => return Err(format!("{} expects a positive count\n{}", arg, USAGE)),
+      },
+      "--photons" => match args.next().and_then(|photons| photons.parse().ok()) {
+        Some(photons) => options.photons = photons,
+        None => return Err(format!("{} expects a count\n{}", arg, USAGE)),
+      },
+      "--photon-radius" => match args.next().and_then(|radius| radius.parse().ok()) {
+        Some(radius) if radius > 0. => options.photon_radius = radius,
+        _ => return Err(format!("{} expects a positive distance\n{}", arg, USAGE)),
+      },
+      _ => return Err(format!("unknown argument {}\n{}", arg, USAGE)),
     }
-    Some(scene) => event_pump
-      .poll_iter()
-      .fold(Some(scene), |last_scene, event| match last_scene {
-        None => None,
-        Some(scene) => handle_input(scene, &mut mouse_clicked, event),
-      }),
   }
+  Ok(options)
 }
 
-fn main() -> Result<(), String> {
+fn render_to_files(
+  world: &World,
+  description: &CameraDescription,
+  camera: Arc<dyn Camera + Send + Sync>,
+  options: &Options,
+) -> Result<(), String> {
+  let render_time = Instant::now();
+  let scene = initial_scene(world, description, camera, options);
+  let mut framebuffer = Framebuffer::new(options.resolution.width, options.resolution.height);
+  for pass in 1..=options.passes {
+    render_frame(&scene, &mut framebuffer, pass);
+  }
+  println!("elapsed(ms): {}", render_time.elapsed().as_millis());
+
+  for output in &options.outputs {
+    let display = DisplayTransform {
+      transfer: output.transfer.unwrap_or(options.display.transfer),
+      ..options.display
+    };
+    framebuffer.save(&output.path, &display)?;
+    println!("wrote {}", output.path.display());
+  }
+  Ok(())
+}
+
+fn run_window(
+  world: &World,
+  description: &CameraDescription,
+  camera: Arc<dyn Camera + Send + Sync>,
+  options: &Options,
+) -> Result<(), String> {
   let sdl_context = sdl2::init()?;
   let video_subsystem = sdl_context.video()?;
 
   let window = video_subsystem
     .window(
       "rust-raytracer",
-      (SCALE * SCREEN_WIDTH) as u32,
-      (SCALE * SCREEN_HEIGHT) as u32,
+      (SCALE * options.resolution.width as f32) as u32,
+      (SCALE * options.resolution.height as f32) as u32,
     )
     .position_centered()
     .opengl()
@@ -221,93 +472,31 @@ fn main() -> Result<(), String> {
   canvas.set_scale(SCALE, SCALE)?;
   let mut event_pump = sdl_context.event_pump()?;
 
-  let shiny_material = Material {
-    albedo: 1.0,
-    color: Color::RGB(0, 0, 0), // TODO
-    material_type: MaterialType::Phong {
-      specular_n: 30,
-      k_diffuse: 0.7,
-      k_specular: 0.3,
-    },
-  };
-  // let mirror_material = Material {
-  //   albedo: 1.0,
-  //   color: Color::RGB(0, 0, 0), // TODO
-  //   material_type: MaterialType::Reflection { reflectivity: 1.0 },
-  // };
-  let transparent_material = Material {
-    albedo: 1.0,
-    color: Color::RGB(0, 0, 0), // TODO
-    material_type: MaterialType::Refraction {
-      refractive_index: 1.03,
-    },
-  };
-  let opaque_material = Material {
-    albedo: 1.0,
-    color: Color::RGB(0, 0, 0), // TODO
-    material_type: MaterialType::Phong {
-      specular_n: 1,
-      k_diffuse: 1.0,
-      k_specular: 0.0,
-    },
-  };
-
-  let sphere = Sphere::new(Point3::new(0.6, 1., -6.), 1., transparent_material);
-  let sphere_b = Sphere::new(Point3::new(3., 2.5, -12.), 2., shiny_material);
-  let sphere_c = Sphere::new(Point3::new(-1., 1., -6.5), 0.5, opaque_material);
-  let floor = Plane::new(
-    Unit::new_normalize(Vector3::new(0., 1., 0.)),
-    Point3::new(0., 0., -10.),
-    (Some(12.), Some(10.)),
-    na::zero(),
-    shiny_material,
-  );
-  let shapes: Vec<&(dyn Shape + Sync)> = vec![&sphere, &sphere_b, &sphere_c, &floor];
-
-  let world = World {
-    shapes,
-    lights: vec![
-      PointLight {
-        ray: Ray {
-          direction: Unit::new_normalize(Vector3::new(0., -1., 0.)),
-          origin: Point3::new(-6., 10., 3.),
-        },
-        color: Color::RGB(200, 140, 0),
-        intensity: 1000.0,
-      },
-      PointLight {
-        ray: Ray {
-          direction: Unit::new_normalize(Vector3::new(0., -1., 0.)),
-          origin: Point3::new(2., 10., -12.),
-        },
-        color: Color::RGB(0, 255, 255),
-        intensity: 500.0,
-      },
-    ],
-  };
-
-  let mut scene: Option<Scene> = None;
+  let mut framebuffer = Framebuffer::new(options.resolution.width, options.resolution.height);
+  let mut scene = initial_scene(world, description, camera, options);
+  // frames accumulated since the view last changed
+  let mut pass = 0;
   let mut mouse_clicked = false;
   'running: loop {
     let loop_time = Instant::now();
-    match get_next_scene(scene, &world, &mut event_pump, &mut mouse_clicked) {
+    match get_next_scene(scene.clone(), &mut event_pump, &mut mouse_clicked) {
       None => break 'running,
-      Some(next_scene) => scene = Some(next_scene),
+      Some(next_scene) => {
+        if !next_scene.same_view(&scene) {
+          pass = 0;
+        }
+        scene = next_scene;
+      }
     };
 
-    let grid: Vec<(i32, i32)> = (0..(SCREEN_WIDTH as i32))
-      .flat_map(|x| (0..(SCREEN_HEIGHT as i32)).map(move |y| (x, y)))
-      .collect();
-
-    let color_grid: Vec<((i32, i32), Color)> = grid
-      .into_par_iter()
-      // .into_iter()
-      .map(|(x, y)| ((x, y), render((x, y), &scene.unwrap())))
-      .collect();
+    pass += 1;
+    render_frame(&scene, &mut framebuffer, pass);
 
-    for ((x, y), color) in color_grid.into_iter() {
-      canvas.set_draw_color(color);
-      canvas.draw_point((x as i32, y as i32)).unwrap();
+    for y in 0..framebuffer.height {
+      for x in 0..framebuffer.width {
+        canvas.set_draw_color(options.display.apply(framebuffer.get(x, y)));
+        canvas.draw_point((x as i32, y as i32))?;
+      }
     }
 
     canvas.present();
@@ -315,8 +504,34 @@ fn main() -> Result<(), String> {
     let micros = loop_time.elapsed().as_micros();
     let fps = 1_000_000 / micros;
 
-    println!("elapsed(ms): {} | fps: {}", micros / 1000, fps,);
+    println!(
+      "elapsed(ms): {} | fps: {} | passes: {}",
+      micros / 1000,
+      fps,
+      pass
+    );
   }
 
   Ok(())
 }
+
+fn main() -> Result<(), String> {
+  let options = parse_args(std::env::args().skip(1))?;
+  let loaded = scene_file::load(&options.scene)?;
+
+  let camera = loaded.camera.camera();
+  let mut world = World::new(
+    loaded.shapes.iter().map(|shape| shape.as_ref()).collect(),
+    loaded.lights,
+    loaded.volumes,
+  );
+  if options.photons > 0 {
+    world.trace_caustics(options.photons, options.photon_radius);
+  }
+
+  if options.outputs.is_empty() {
+    run_window(&world, &loaded.camera, camera, &options)
+  } else {
+    render_to_files(&world, &loaded.camera, camera, &options)
+  }
+}
@@ -1,24 +1,157 @@
 use crate::color::Color;
+use crate::sampling::orthonormal_basis;
+use crate::texture::Texture;
+use na::{Point2, Point3, Unit, Vector2, Vector3};
+use std::sync::Arc;
 
 #[allow(dead_code)]
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum MaterialType {
+  // `roughness` spreads reflected and refracted rays over a GGX lobe, 0
+  // being a perfectly smooth surface
   Reflection {
     reflectivity: f32,
+    roughness: f32,
   },
   Refraction {
     refractive_index: f32,
+    roughness: f32,
+    // of the medium, per unit of distance light travels through it
+    absorption: Color,
   },
   Phong {
     k_specular: f32,
     k_diffuse: f32,
     specular_n: i32,
   },
+  // microfacet metallic-roughness model, its base color being the
+  // material's color, see `brdf::Microfacet`
+  Pbr {
+    metallic: f32,
+    roughness: f32,
+    specular: f32,
+  },
+  // layered materials, their own color and bump being unused:
+  // `first` where `mask` is black, `second` where it is white, blended in
+  // between
+  Mix {
+    first: Arc<Material>,
+    second: Arc<Material>,
+    mask: Arc<dyn Texture>,
+  },
+  // a clear varnish over `base`, reflecting more at grazing angles; `weight`
+  // scales it down from a full coat at 1
+  ClearCoat {
+    base: Arc<Material>,
+    refractive_index: f32,
+    roughness: f32,
+    weight: f32,
+  },
 }
 
-#[derive(Debug, Copy, Clone)]
+// details too small to model, tilting the normal used for shading
+#[derive(Debug, Clone)]
+pub enum Bump {
+  // tangent space normals, encoded as linear colors: red along the tangent
+  // (u), green along the bitangent (v) and blue away from the surface
+  NormalMap {
+    texture: Arc<dyn Texture>,
+    strength: f32,
+  },
+  // heights, the average of the color components, whose slopes tilt the normal
+  HeightMap {
+    texture: Arc<dyn Texture>,
+    strength: f32,
+  },
+}
+
+// step for the finite differences of height maps, in texture coordinates and
+// object space units
+const HEIGHT_MAP_STEP: f32 = 1e-3;
+
+impl Bump {
+  // `tangent` points towards increasing u, the bitangent being n × tangent
+  pub fn perturb(
+    &self,
+    uv: &Point2<f32>,
+    point: &Point3<f32>,
+    normal: &Unit<Vector3<f32>>,
+    tangent: &Unit<Vector3<f32>>,
+  ) -> Unit<Vector3<f32>> {
+    // make the frame orthonormal, as interpolated normals needn't be
+    // perpendicular to the tangent
+    let tangent = tangent.into_inner() - normal.into_inner() * normal.dot(tangent);
+    let tangent = match Unit::try_new(tangent, 1e-6) {
+      Some(tangent) => tangent.into_inner(),
+      None => orthonormal_basis(normal).0,
+    };
+    let bitangent = normal.cross(&tangent);
+
+    match self {
+      Bump::NormalMap { texture, strength } => {
+        let color = texture.color_at(uv, point);
+        let (x, y, z) = (2. * color.r - 1., 2. * color.g - 1., 2. * color.b - 1.);
+        Unit::new_normalize(
+          tangent * (x * strength) + bitangent * (y * strength) + normal.into_inner() * z,
+        )
+      }
+      Bump::HeightMap { texture, strength } => {
+        let height = |uv: Point2<f32>, point: Point3<f32>| {
+          let color = texture.color_at(&uv, &point);
+          (color.r + color.g + color.b) / 3.
+        };
+        let h = height(*uv, *point);
+        let step = HEIGHT_MAP_STEP;
+        let dh_du = (height(uv + Vector2::x() * step, point + tangent * step) - h) / step;
+        let dh_dv = (height(uv + Vector2::y() * step, point + bitangent * step) - h) / step;
+        Unit::new_normalize(normal.into_inner() - (tangent * dh_du + bitangent * dh_dv) * *strength)
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
 pub struct Material {
-  pub color: Color,
+  pub color: Arc<dyn Texture>,
   pub albedo: f32,
   pub material_type: MaterialType,
+  pub bump: Option<Bump>,
+  // light given off by the surface towards its front, in linear RGB
+  pub emission: Color,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::texture::Gradient;
+  use crate::texture::{Axis, SolidColor};
+
+  #[test]
+  fn test_bump() {
+    let normal = Vector3::z_axis();
+    let tangent = Vector3::x_axis();
+    let (uv, point) = (Point2::new(0.5, 0.5), Point3::origin());
+
+    // a flat normal map keeps the normal
+    let flat = Bump::NormalMap {
+      texture: Arc::new(SolidColor(Color::new(0.5, 0.5, 1.))),
+      strength: 1.,
+    };
+    assert!(
+      (flat.perturb(&uv, &point, &normal, &tangent).into_inner() - normal.into_inner()).norm()
+        < 1e-6
+    );
+
+    // heights rising with u tilt the normal back towards -u
+    let slope = Bump::HeightMap {
+      texture: Arc::new(Gradient {
+        start: Color::zero(),
+        end: Color::new(1., 1., 1.),
+        axis: Axis::U,
+      }),
+      strength: 1.,
+    };
+    let perturbed = slope.perturb(&uv, &point, &normal, &tangent);
+    assert!((perturbed.into_inner() - Vector3::new(-1., 0., 1.).normalize()).norm() < 1e-2);
+  }
 }
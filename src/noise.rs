@@ -0,0 +1,149 @@
+use na::Point3;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+// Ken Perlin's improved noise, a smooth pseudo random function of space
+// ranging over about [-1, 1], with a permutation picked by `seed`.
+// https://mrl.cs.nyu.edu/~perlin/noise/
+#[derive(Clone)]
+pub struct Perlin {
+  seed: u64,
+  // 0..256 shuffled, twice so that lookups can overflow without wrapping
+  permutation: Vec<u8>,
+}
+
+impl std::fmt::Debug for Perlin {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Perlin").field("seed", &self.seed).finish()
+  }
+}
+
+fn fade(t: f32) -> f32 {
+  t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+  a + t * (b - a)
+}
+
+// dot product with one of 12 gradients pointing to the edges of a cube
+fn gradient(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+  let h = hash & 15;
+  let u = if h < 8 { x } else { y };
+  let v = match h {
+    0..=3 => y,
+    12 | 14 => x,
+    _ => z,
+  };
+  (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+impl Perlin {
+  pub fn new(seed: u64) -> Perlin {
+    let mut permutation: Vec<u8> = (0..=255).collect();
+    permutation.shuffle(&mut StdRng::seed_from_u64(seed));
+    permutation.extend_from_within(..);
+    Perlin { seed, permutation }
+  }
+
+  pub fn noise(&self, point: &Point3<f32>) -> f32 {
+    let p = &self.permutation;
+    let (x0, y0, z0) = (point.x.floor(), point.y.floor(), point.z.floor());
+    // position in the unit cube of the lattice, and which cube it is
+    let (x, y, z) = (point.x - x0, point.y - y0, point.z - z0);
+    let lattice = |c: f32| (c as i64 & 255) as usize;
+    let (xi, yi, zi) = (lattice(x0), lattice(y0), lattice(z0));
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+
+    let a = p[xi] as usize + yi;
+    let (aa, ab) = (p[a] as usize + zi, p[a + 1] as usize + zi);
+    let b = p[xi + 1] as usize + yi;
+    let (ba, bb) = (p[b] as usize + zi, p[b + 1] as usize + zi);
+
+    lerp(
+      w,
+      lerp(
+        v,
+        lerp(u, gradient(p[aa], x, y, z), gradient(p[ba], x - 1., y, z)),
+        lerp(
+          u,
+          gradient(p[ab], x, y - 1., z),
+          gradient(p[bb], x - 1., y - 1., z),
+        ),
+      ),
+      lerp(
+        v,
+        lerp(
+          u,
+          gradient(p[aa + 1], x, y, z - 1.),
+          gradient(p[ba + 1], x - 1., y, z - 1.),
+        ),
+        lerp(
+          u,
+          gradient(p[ab + 1], x, y - 1., z - 1.),
+          gradient(p[bb + 1], x - 1., y - 1., z - 1.),
+        ),
+      ),
+    )
+  }
+
+  // fractional Brownian motion: octaves of noise, each twice the frequency
+  // and half the amplitude of the previous one, over about [-1, 1]
+  pub fn fbm(&self, point: &Point3<f32>, octaves: u32) -> f32 {
+    self.sum_octaves(point, octaves, |noise| noise)
+  }
+
+  // like `fbm` with the absolute value of each octave, giving creases where
+  // the noise crosses zero; over about [0, 1]
+  pub fn turbulence(&self, point: &Point3<f32>, octaves: u32) -> f32 {
+    self.sum_octaves(point, octaves, f32::abs)
+  }
+
+  fn sum_octaves(&self, point: &Point3<f32>, octaves: u32, f: impl Fn(f32) -> f32) -> f32 {
+    let mut sum = 0.;
+    let mut total_amplitude = 0.;
+    let mut amplitude = 1.;
+    let mut frequency = 1.;
+    for _ in 0..octaves {
+      sum += amplitude * f(self.noise(&(point * frequency)));
+      total_amplitude += amplitude;
+      amplitude *= 0.5;
+      frequency *= 2.;
+    }
+    sum / total_amplitude
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_noise() {
+    let perlin = Perlin::new(7);
+    let points: Vec<Point3<f32>> = (0..1000)
+      .map(|i| {
+        let i = i as f32;
+        Point3::new(i * 0.137, (i * 0.71).sin() * 9., i * -0.053)
+      })
+      .collect();
+    for point in &points {
+      // zero on the lattice
+      let corner = Point3::from(point.coords.map(f32::floor));
+      assert_eq!(perlin.noise(&corner), 0.);
+      let noise = perlin.noise(point);
+      assert!((-1.1..=1.1).contains(&noise));
+      // continuous
+      let nearby = perlin.noise(&(point + na::Vector3::repeat(1e-3)));
+      assert!((noise - nearby).abs() < 0.01);
+      assert!((0. ..=1.).contains(&perlin.turbulence(point, 4)));
+    }
+    // seeds give different noise, the same seed the same one
+    let other = Perlin::new(8);
+    assert!(points.iter().any(|p| perlin.noise(p) != other.noise(p)));
+    assert!(points
+      .iter()
+      .all(|p| perlin.fbm(p, 5) == Perlin::new(7).fbm(p, 5)));
+  }
+}
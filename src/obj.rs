@@ -0,0 +1,395 @@
+use crate::color::Color;
+use crate::material::{Material, MaterialType};
+use crate::shapes::mesh::Vertex;
+use crate::texture::SolidColor;
+use na::{Point2, Point3, Unit, Vector3};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+// Wavefront OBJ import, with materials from the MTL libraries it references.
+// http://paulbourke.net/dataformats/obj/
+// http://paulbourke.net/dataformats/mtl/
+
+// geometry of one `o` object of the file, ready for `Mesh::new`
+#[derive(Debug, Clone)]
+pub struct MeshData {
+  pub vertices: Vec<Vertex>,
+  pub triangles: Vec<[usize; 3]>,
+  // indices into `ObjFile::materials`
+  pub face_materials: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjFile {
+  pub meshes: Vec<MeshData>,
+  // the first one is used by faces that come before any `usemtl`
+  pub materials: Vec<Material>,
+}
+
+pub fn load(path: &Path, default_material: Material) -> Result<ObjFile, String> {
+  let read = |path: &Path| {
+    fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))
+  };
+  let directory = path.parent().unwrap_or_else(|| Path::new(""));
+  let load_library = |name: &str| {
+    let library_path = directory.join(name);
+    parse_mtl(&read(&library_path)?).map_err(|e| format!("{}:{}", library_path.display(), e))
+  };
+  parse_obj(&read(path)?, default_material, load_library)
+    .map_err(|e| format!("{}:{}", path.display(), e))
+}
+
+// indices are 1-based, or relative to the end of the list when negative
+fn resolve_index(index: &str, len: usize) -> Result<usize, String> {
+  let index: i64 = index
+    .parse()
+    .map_err(|_| format!("invalid index \"{}\"", index))?;
+  let resolved = if index < 0 {
+    len as i64 + index
+  } else {
+    index - 1
+  };
+  if resolved < 0 || resolved >= len as i64 {
+    return Err(format!("index {} out of range", index));
+  }
+  Ok(resolved as usize)
+}
+
+// the first `count` arguments, extra ones (like an optional w) are ignored
+fn parse_floats(arguments: &[&str], count: usize) -> Result<Vec<f32>, String> {
+  if arguments.len() < count {
+    return Err(format!("expected {} numbers", count));
+  }
+  arguments[..count]
+    .iter()
+    .map(|argument| {
+      argument
+        .parse()
+        .map_err(|_| format!("invalid number \"{}\"", argument))
+    })
+    .collect()
+}
+
+pub fn parse_obj(
+  source: &str,
+  default_material: Material,
+  mut load_library: impl FnMut(&str) -> Result<Vec<(String, Material)>, String>,
+) -> Result<ObjFile, String> {
+  let mut positions: Vec<Point3<f32>> = vec![];
+  let mut normals: Vec<Unit<Vector3<f32>>> = vec![];
+  let mut uvs: Vec<Point2<f32>> = vec![];
+
+  let mut materials = vec![default_material];
+  let mut material_names: HashMap<String, usize> = HashMap::new();
+  let mut current_material = 0;
+
+  let new_mesh = || MeshData {
+    vertices: vec![],
+    triangles: vec![],
+    face_materials: vec![],
+  };
+  let mut meshes = vec![new_mesh()];
+  // (position, uv, normal) of each vertex of the current mesh
+  let mut vertex_indices: HashMap<(usize, Option<usize>, Option<usize>), usize> = HashMap::new();
+
+  for (line_number, line) in source.lines().enumerate() {
+    let at_line = |e: String| format!("{}: {}", line_number + 1, e);
+    let line = line.split('#').next().unwrap_or("");
+    let mut words = line.split_whitespace();
+    let keyword = match words.next() {
+      Some(keyword) => keyword,
+      None => continue,
+    };
+    let arguments: Vec<&str> = words.collect();
+
+    match keyword {
+      "v" => {
+        let p = parse_floats(&arguments, 3).map_err(at_line)?;
+        positions.push(Point3::new(p[0], p[1], p[2]));
+      }
+      "vn" => {
+        let n = parse_floats(&arguments, 3).map_err(at_line)?;
+        normals.push(Unit::new_normalize(Vector3::new(n[0], n[1], n[2])));
+      }
+      "vt" => {
+        let uv = parse_floats(&arguments, 2).map_err(at_line)?;
+        uvs.push(Point2::new(uv[0], uv[1]));
+      }
+      "o" => {
+        meshes.push(new_mesh());
+        vertex_indices.clear();
+      }
+      "mtllib" => {
+        for library in arguments {
+          for (name, material) in load_library(library)? {
+            material_names.insert(name, materials.len());
+            materials.push(material);
+          }
+        }
+      }
+      "usemtl" => {
+        let name = arguments.first().unwrap_or(&"");
+        current_material = *material_names
+          .get(*name)
+          .ok_or_else(|| at_line(format!("unknown material \"{}\"", name)))?;
+      }
+      "f" => {
+        if arguments.len() < 3 {
+          return Err(at_line("a face needs at least 3 vertices".to_string()));
+        }
+        let mesh = meshes.last_mut().unwrap();
+        let mut face = vec![];
+        for argument in arguments {
+          // v, v/vt, v//vn or v/vt/vn
+          let mut parts = argument.split('/');
+          let position =
+            resolve_index(parts.next().unwrap_or(""), positions.len()).map_err(at_line)?;
+          let uv = match parts.next() {
+            Some(index) if !index.is_empty() => {
+              Some(resolve_index(index, uvs.len()).map_err(at_line)?)
+            }
+            _ => None,
+          };
+          let normal = match parts.next() {
+            Some(index) if !index.is_empty() => {
+              Some(resolve_index(index, normals.len()).map_err(at_line)?)
+            }
+            _ => None,
+          };
+          let vertex = *vertex_indices
+            .entry((position, uv, normal))
+            .or_insert_with(|| {
+              mesh.vertices.push(Vertex {
+                position: positions[position],
+                normal: normal.map(|i| normals[i]),
+                uv: uv.map(|i| uvs[i]),
+              });
+              mesh.vertices.len() - 1
+            });
+          face.push(vertex);
+        }
+        // polygons are fanned out into triangles
+        for i in 1..face.len() - 1 {
+          mesh.triangles.push([face[0], face[i], face[i + 1]]);
+          mesh.face_materials.push(current_material);
+        }
+      }
+      // groups, smoothing groups, lines...
+      _ => {}
+    }
+  }
+
+  Ok(ObjFile {
+    meshes: meshes
+      .into_iter()
+      .filter(|mesh| !mesh.triangles.is_empty())
+      .collect(),
+    materials,
+  })
+}
+
+#[derive(Debug, Clone)]
+struct MtlEntry {
+  diffuse: (f32, f32, f32),
+  specular: (f32, f32, f32),
+  emission: (f32, f32, f32),
+  shininess: f32,
+  dissolve: f32,
+  refractive_index: f32,
+  illumination: i32,
+  // from the PBR extension of MTL, any of them making the material a
+  // metallic-roughness one
+  roughness: Option<f32>,
+  metallic: Option<f32>,
+}
+
+impl Default for MtlEntry {
+  fn default() -> Self {
+    MtlEntry {
+      diffuse: (0.8, 0.8, 0.8),
+      specular: (0., 0., 0.),
+      emission: (0., 0., 0.),
+      shininess: 1.,
+      dissolve: 1.,
+      refractive_index: 1.,
+      illumination: 2,
+      roughness: None,
+      metallic: None,
+    }
+  }
+}
+
+impl From<&MtlEntry> for Material {
+  fn from(entry: &MtlEntry) -> Material {
+    let (r, g, b) = entry.diffuse;
+    let (sr, sg, sb) = entry.specular;
+    let (er, eg, eb) = entry.emission;
+    let specular = (sr + sg + sb) / 3.;
+    let material_type = match entry.illumination {
+      // transparency, with refraction for 6, 7 and 9
+      4 | 6 | 7 | 9 => MaterialType::Refraction {
+        refractive_index: entry.refractive_index,
+        roughness: entry.roughness.unwrap_or(0.),
+        absorption: Color::zero(),
+      },
+      _ if entry.dissolve < 1. => MaterialType::Refraction {
+        refractive_index: entry.refractive_index,
+        roughness: entry.roughness.unwrap_or(0.),
+        absorption: Color::zero(),
+      },
+      _ if entry.roughness.is_some() || entry.metallic.is_some() => MaterialType::Pbr {
+        metallic: entry.metallic.unwrap_or(0.),
+        roughness: entry.roughness.unwrap_or(1.),
+        specular: 0.5,
+      },
+      // ray traced reflection
+      3 | 5 | 8 => MaterialType::Reflection {
+        reflectivity: specular,
+        roughness: entry.roughness.unwrap_or(0.),
+      },
+      _ => MaterialType::Phong {
+        k_specular: specular,
+        k_diffuse: 1.,
+        specular_n: entry.shininess.round().max(1.) as i32,
+      },
+    };
+    Material {
+      color: Arc::new(SolidColor(Color::from_srgb(r, g, b))),
+      albedo: 1.,
+      material_type,
+      bump: None,
+      emission: Color::from_srgb(er, eg, eb),
+    }
+  }
+}
+
+pub fn parse_mtl(source: &str) -> Result<Vec<(String, Material)>, String> {
+  let mut entries: Vec<(String, MtlEntry)> = vec![];
+  for (line_number, line) in source.lines().enumerate() {
+    let at_line = |e: String| format!("{}: {}", line_number + 1, e);
+    let line = line.split('#').next().unwrap_or("");
+    let mut words = line.split_whitespace();
+    let keyword = match words.next() {
+      Some(keyword) => keyword,
+      None => continue,
+    };
+    let arguments: Vec<&str> = words.collect();
+
+    if keyword == "newmtl" {
+      let name = arguments.first().unwrap_or(&"");
+      entries.push((name.to_string(), MtlEntry::default()));
+      continue;
+    }
+    let entry = match entries.last_mut() {
+      Some((_, entry)) => entry,
+      None => return Err(at_line(format!("\"{}\" before any newmtl", keyword))),
+    };
+    match keyword {
+      "Kd" => {
+        let c = parse_floats(&arguments, 3).map_err(at_line)?;
+        entry.diffuse = (c[0], c[1], c[2]);
+      }
+      "Ks" => {
+        let c = parse_floats(&arguments, 3).map_err(at_line)?;
+        entry.specular = (c[0], c[1], c[2]);
+      }
+      "Ke" => {
+        let c = parse_floats(&arguments, 3).map_err(at_line)?;
+        entry.emission = (c[0], c[1], c[2]);
+      }
+      "Ns" | "d" | "Tr" | "Ni" | "illum" | "Pr" | "Pm" => {
+        let value = parse_floats(&arguments, 1).map_err(at_line)?[0];
+        match keyword {
+          "Ns" => entry.shininess = value,
+          "d" => entry.dissolve = value,
+          "Tr" => entry.dissolve = 1. - value,
+          "Ni" => entry.refractive_index = value,
+          "Pr" => entry.roughness = Some(value),
+          "Pm" => entry.metallic = Some(value),
+          _ => entry.illumination = value as i32,
+        }
+      }
+      // ambient and texture maps aren't supported
+      _ => {}
+    }
+  }
+  Ok(
+    entries
+      .iter()
+      .map(|(name, entry)| (name.clone(), entry.into()))
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::shapes::test_helpers::material;
+
+  #[test]
+  fn test_parse_obj() {
+    let obj = parse_obj(
+      "
+mtllib cube.mtl
+o quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 1
+vn 0 0 1
+f 1 2 3 4
+o triangle
+usemtl glass
+f -4/1/1 -3/2/1 -2/2/1 # relative indices
+",
+      material(),
+      |library| {
+        assert_eq!(library, "cube.mtl");
+        parse_mtl(
+          "
+newmtl glass
+Kd 1 1 1
+illum 7
+Ni 1.5
+",
+        )
+      },
+    )
+    .unwrap();
+
+    assert_eq!(obj.materials.len(), 2);
+    assert!(matches!(
+      obj.materials[1].material_type,
+      MaterialType::Refraction {
+        refractive_index,
+        roughness,
+        ..
+      } if refractive_index == 1.5 && roughness == 0.
+    ));
+
+    assert_eq!(obj.meshes.len(), 2);
+    let quad = &obj.meshes[0];
+    assert_eq!(quad.vertices.len(), 4);
+    assert_eq!(quad.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+    assert_eq!(quad.face_materials, vec![0, 0]);
+
+    let triangle = &obj.meshes[1];
+    assert_eq!(triangle.triangles, vec![[0, 1, 2]]);
+    assert_eq!(triangle.face_materials, vec![1]);
+    assert_eq!(triangle.vertices[1].position, Point3::new(1., 0., 0.));
+    assert_eq!(triangle.vertices[1].uv, Some(Point2::new(1., 1.)));
+    assert!(triangle.vertices[1].normal.is_some());
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    let error = parse_obj("v 0 0 0\nf 1 2 3\n", material(), |_| Ok(vec![])).unwrap_err();
+    assert_eq!(error, "2: index 2 out of range");
+    let error = parse_obj("usemtl wood\n", material(), |_| Ok(vec![])).unwrap_err();
+    assert_eq!(error, "1: unknown material \"wood\"");
+  }
+}
@@ -0,0 +1,120 @@
+use crate::color::Color;
+use na::{Point3, Unit, Vector3};
+
+// Light that reached a surface, after going through glass or off mirrors
+// for caustics
+#[derive(Debug, Copy, Clone)]
+pub struct Photon {
+  pub position: Point3<f32>,
+  // towards where it came from
+  pub direction: Unit<Vector3<f32>>,
+  pub power: Color,
+}
+
+// Photons in a balanced kd-tree, kept in a single array: the photon in the
+// middle of every range splits the others along its axis, those before it
+// being its left subtree and those after its right one.
+// http://graphics.stanford.edu/~henrik/papers/book/
+#[derive(Debug, Default)]
+pub struct PhotonMap {
+  photons: Vec<Photon>,
+  axes: Vec<u8>,
+  // within which photons are gathered around a point
+  pub radius: f32,
+}
+
+impl PhotonMap {
+  pub fn new(mut photons: Vec<Photon>, radius: f32) -> PhotonMap {
+    let mut axes = vec![0; photons.len()];
+    build(&mut photons, &mut axes);
+    PhotonMap {
+      photons,
+      axes,
+      radius,
+    }
+  }
+
+  // calls `f` with every photon within `radius` of `point`
+  pub fn gather(&self, point: &Point3<f32>, mut f: impl FnMut(&Photon)) {
+    self.gather_in(0, self.photons.len(), point, &mut f);
+  }
+
+  fn gather_in(&self, start: usize, end: usize, point: &Point3<f32>, f: &mut impl FnMut(&Photon)) {
+    if start >= end {
+      return;
+    }
+    let middle = start + (end - start) / 2;
+    let photon = &self.photons[middle];
+    let squared_radius = self.radius * self.radius;
+    if (photon.position - point).norm_squared() <= squared_radius {
+      f(photon);
+    }
+    // the side of the split the point is on first, the other only when the
+    // sphere around the point crosses it
+    let axis = self.axes[middle] as usize;
+    let offset = point[axis] - photon.position[axis];
+    let (near, far) = if offset < 0. {
+      ((start, middle), (middle + 1, end))
+    } else {
+      ((middle + 1, end), (start, middle))
+    };
+    self.gather_in(near.0, near.1, point, f);
+    if offset * offset <= squared_radius {
+      self.gather_in(far.0, far.1, point, f);
+    }
+  }
+}
+
+// orders `photons` into a tree, split along the axis they spread the most on
+fn build(photons: &mut [Photon], axes: &mut [u8]) {
+  if photons.is_empty() {
+    return;
+  }
+  let first = photons[0].position;
+  let (min, max) = photons.iter().fold((first, first), |(min, max), photon| {
+    (
+      min.coords.inf(&photon.position.coords).into(),
+      max.coords.sup(&photon.position.coords).into(),
+    )
+  });
+  let axis = (max - min).imax();
+  let middle = photons.len() / 2;
+  photons.select_nth_unstable_by(middle, |a, b| a.position[axis].total_cmp(&b.position[axis]));
+  axes[middle] = axis as u8;
+  let (left, right) = photons.split_at_mut(middle);
+  let (left_axes, right_axes) = axes.split_at_mut(middle);
+  build(left, left_axes);
+  build(&mut right[1..], &mut right_axes[1..]);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::rngs::StdRng;
+  use rand::{Rng, SeedableRng};
+
+  #[test]
+  fn test_gather() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let photons: Vec<Photon> = (0..2000)
+      .map(|_| Photon {
+        position: Point3::new(rng.gen(), rng.gen(), rng.gen::<f32>() * 0.1),
+        direction: Vector3::z_axis(),
+        power: Color::new(1., 1., 1.),
+      })
+      .collect();
+    let map = PhotonMap::new(photons.clone(), 0.1);
+
+    // the same photons as looking at all of them
+    for _ in 0..20 {
+      let point = Point3::new(rng.gen(), rng.gen(), 0.05);
+      let mut found = 0;
+      map.gather(&point, |_| found += 1);
+      let expected = photons
+        .iter()
+        .filter(|photon| (photon.position - point).norm() <= 0.1)
+        .count();
+      assert_eq!(found, expected);
+    }
+  }
+}
@@ -0,0 +1,388 @@
+use na::{Unit, Vector3};
+use rand::Rng;
+use std::f32::consts::PI;
+
+// two unit vectors perpendicular to `n` and to each other
+// https://graphics.pixar.com/library/OrthonormalB/paper.pdf
+pub fn orthonormal_basis(n: &Unit<Vector3<f32>>) -> (Vector3<f32>, Vector3<f32>) {
+  let sign = 1f32.copysign(n.z);
+  let a = -1. / (sign + n.z);
+  let b = n.x * n.y * a;
+  (
+    Vector3::new(1. + sign * n.x * n.x * a, sign * b, -sign * n.x),
+    Vector3::new(b, sign + n.y * n.y * a, -n.y),
+  )
+}
+
+// direction around `n` with a probability density of cos(theta) / PI
+pub fn cosine_sample_hemisphere(n: &Unit<Vector3<f32>>, rng: &mut impl Rng) -> Unit<Vector3<f32>> {
+  let (u, v): (f32, f32) = (rng.gen(), rng.gen());
+  let radius = u.sqrt();
+  let angle = 2. * PI * v;
+  let (tangent, bitangent) = orthonormal_basis(n);
+  Unit::new_normalize(
+    tangent * (radius * angle.cos())
+      + bitangent * (radius * angle.sin())
+      + n.into_inner() * (1. - u).max(0.).sqrt(),
+  )
+}
+
+// uniform point of the unit disk for a point of the unit square, keeping
+// the center at the center
+// https://pbr-book.org/3ed-2018/Monte_Carlo_Integration/2D_Sampling_with_Multidimensional_Transformations#ConcentricSampleDisk
+pub fn concentric_sample_disk((u, v): (f32, f32)) -> (f32, f32) {
+  let (x, y) = (2. * u - 1., 2. * v - 1.);
+  if x == 0. && y == 0. {
+    return (0., 0.);
+  }
+  let (radius, angle) = if x.abs() > y.abs() {
+    (x, PI / 4. * (y / x))
+  } else {
+    (y, PI / 2. - PI / 4. * (x / y))
+  };
+  (radius * angle.cos(), radius * angle.sin())
+}
+
+// uniform direction for a point of the unit square
+pub fn uniform_sample_sphere((u, v): (f32, f32)) -> Unit<Vector3<f32>> {
+  let z = 1. - 2. * u;
+  let ring = (1. - z * z).max(0.).sqrt();
+  let angle = 2. * PI * v;
+  Unit::new_unchecked(Vector3::new(ring * angle.cos(), ring * angle.sin(), z))
+}
+
+// uniform point of the regular polygon inscribed in the unit circle with a
+// vertex at `rotation` radians. Each of the disk's sectors is stretched onto
+// a triangle of the polygon, spacing angles so that the area stays uniform.
+pub fn sample_polygon(sides: u32, rotation: f32, square_point: (f32, f32)) -> (f32, f32) {
+  let (x, y) = concentric_sample_disk(square_point);
+  let radius = (x * x + y * y).sqrt();
+  let half_sector = PI / sides as f32;
+  let from_vertex = y.atan2(x) - rotation;
+  let sector = (from_vertex / (2. * half_sector)).floor();
+  let in_sector = from_vertex - sector * 2. * half_sector;
+  // angle from the apothem, whose length is the cosine of half a sector
+  let offset = ((in_sector / half_sector - 1.) * half_sector.tan()).atan();
+  let radius = radius * half_sector.cos() / offset.cos();
+  let angle = rotation + (2. * sector + 1.) * half_sector + offset;
+  (radius * angle.cos(), radius * angle.sin())
+}
+
+// where the samples of a pixel go, as points of the unit square
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SamplePattern {
+  // centers of a grid of cells
+  Regular,
+  // one random point in each cell of the grid
+  Jittered,
+  // low discrepancy sequences, randomly shifted for each pixel so that
+  // neighbours don't share the same structure
+  Halton,
+  Sobol,
+}
+
+impl SamplePattern {
+  pub fn from_name(name: &str) -> Option<SamplePattern> {
+    match name {
+      "regular" => Some(SamplePattern::Regular),
+      "jittered" => Some(SamplePattern::Jittered),
+      "halton" => Some(SamplePattern::Halton),
+      "sobol" => Some(SamplePattern::Sobol),
+      _ => None,
+    }
+  }
+
+  // `count` points of [0, 1)²: the `pass`-th batch of them (starting at 0),
+  // so that sequences keep going over progressive passes
+  pub fn generate(&self, count: u32, pass: u32, pixel: u32, rng: &mut impl Rng) -> Vec<(f32, f32)> {
+    use SamplePattern::*;
+    match self {
+      Regular | Jittered => {
+        let columns = (count as f32).sqrt().ceil() as u32;
+        let rows = count.div_ceil(columns);
+        (0..count)
+          .map(|i| {
+            let (dx, dy) = match self {
+              Jittered => (rng.gen(), rng.gen()),
+              _ => (0.5, 0.5),
+            };
+            (
+              ((i % columns) as f32 + dx) / columns as f32,
+              ((i / columns) as f32 + dy) / rows as f32,
+            )
+          })
+          .collect()
+      }
+      Halton | Sobol => {
+        let shift = (to_unit(hash(pixel)), to_unit(hash(pixel ^ 0x9e37_79b9)));
+        (0..count)
+          .map(|i| {
+            let index = pass * count + i;
+            let (x, y) = match self {
+              Halton => (radical_inverse(index, 2), radical_inverse(index, 3)),
+              _ => (
+                to_unit(index.reverse_bits()),
+                to_unit(sobol_second_dimension(index)),
+              ),
+            };
+            ((x + shift.0).fract(), (y + shift.1).fract())
+          })
+          .collect()
+      }
+    }
+  }
+}
+
+fn radical_inverse(mut index: u32, base: u32) -> f32 {
+  let inverse_base = 1. / base as f32;
+  let mut scale = inverse_base;
+  let mut result = 0.;
+  while index > 0 {
+    result += (index % base) as f32 * scale;
+    index /= base;
+    scale *= inverse_base;
+  }
+  result
+}
+
+// the first dimension of Sobol's sequence is the base 2 radical inverse
+// https://www.semanticscholar.org/paper/Efficient-Multidimensional-Sampling-Kollig-Keller
+fn sobol_second_dimension(mut index: u32) -> u32 {
+  let mut v = 1 << 31;
+  let mut result = 0;
+  while index != 0 {
+    if index & 1 != 0 {
+      result ^= v;
+    }
+    index >>= 1;
+    v ^= v >> 1;
+  }
+  result
+}
+
+fn to_unit(bits: u32) -> f32 {
+  (bits >> 8) as f32 / (1 << 24) as f32
+}
+
+// https://nullprogram.com/blog/2018/07/31/
+fn hash(mut x: u32) -> u32 {
+  x ^= x >> 16;
+  x = x.wrapping_mul(0x7feb_352d);
+  x ^= x >> 15;
+  x = x.wrapping_mul(0x846c_a68b);
+  x ^= x >> 16;
+  x
+}
+
+// how much a sample counts for a pixel, given its offset from the pixel
+// center. Filters are separable and zero beyond their radius.
+// https://pbr-book.org/3ed-2018/Sampling_and_Reconstruction/Image_Reconstruction
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Filter {
+  Box,
+  Tent,
+  Gaussian,
+  // Mitchell–Netravali with B = C = 1/3, sharper but with negative lobes
+  Mitchell,
+}
+
+const GAUSSIAN_ALPHA: f32 = 2.;
+
+impl Filter {
+  pub fn from_name(name: &str) -> Option<Filter> {
+    match name {
+      "box" => Some(Filter::Box),
+      "tent" => Some(Filter::Tent),
+      "gaussian" => Some(Filter::Gaussian),
+      "mitchell" => Some(Filter::Mitchell),
+      _ => None,
+    }
+  }
+
+  // in pixels
+  pub fn radius(&self) -> f32 {
+    match self {
+      Filter::Box => 0.5,
+      Filter::Tent => 1.,
+      Filter::Gaussian => 1.5,
+      Filter::Mitchell => 2.,
+    }
+  }
+
+  pub fn weight(&self, x: f32, y: f32) -> f32 {
+    self.weight_1d(x) * self.weight_1d(y)
+  }
+
+  fn weight_1d(&self, x: f32) -> f32 {
+    let radius = self.radius();
+    let x = x.abs();
+    if x > radius {
+      return 0.;
+    }
+    match self {
+      Filter::Box => 1.,
+      Filter::Tent => radius - x,
+      // shifted down to reach zero at the radius
+      Filter::Gaussian => {
+        (-GAUSSIAN_ALPHA * x * x).exp() - (-GAUSSIAN_ALPHA * radius * radius).exp()
+      }
+      Filter::Mitchell => {
+        let (b, c) = (1. / 3., 1. / 3.);
+        let polynomial = if x < 1. {
+          (12. - 9. * b - 6. * c) * x.powi(3) + (-18. + 12. * b + 6. * c) * x * x + (6. - 2. * b)
+        } else {
+          (-b - 6. * c) * x.powi(3)
+            + (6. * b + 30. * c) * x * x
+            + (-12. * b - 48. * c) * x
+            + (8. * b + 24. * c)
+        };
+        polynomial / 6.
+      }
+    }
+  }
+}
+
+// how many rays go through each pixel, and how they are combined
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PixelSampler {
+  pub pattern: SamplePattern,
+  pub samples: u32,
+  pub filter: Filter,
+}
+
+impl Default for PixelSampler {
+  // a single ray through the pixel center
+  fn default() -> Self {
+    PixelSampler {
+      pattern: SamplePattern::Regular,
+      samples: 1,
+      filter: Filter::Box,
+    }
+  }
+}
+
+impl PixelSampler {
+  // offsets from the pixel center, spread over the filter's footprint, with
+  // the weight of each
+  pub fn offsets(&self, pass: u32, pixel: u32, rng: &mut impl Rng) -> Vec<((f32, f32), f32)> {
+    let radius = self.filter.radius();
+    self
+      .pattern
+      .generate(self.samples, pass, pixel, rng)
+      .into_iter()
+      .map(|(u, v)| {
+        let (x, y) = ((2. * u - 1.) * radius, (2. * v - 1.) * radius);
+        ((x, y), self.filter.weight(x, y))
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_cosine_sample_hemisphere() {
+    let mut rng = rand::thread_rng();
+    for n in &[
+      Vector3::new(0., 1., 0.),
+      Vector3::new(0., 0., -1.),
+      Vector3::new(1., -2., 0.5),
+    ] {
+      let n = Unit::new_normalize(*n);
+      let (t, b) = orthonormal_basis(&n);
+      assert!(t.dot(&n).abs() < 1e-5 && b.dot(&n).abs() < 1e-5 && t.dot(&b).abs() < 1e-5);
+
+      // E[cos(theta)] = 2/3 for a cosine-weighted hemisphere
+      let samples = 20000;
+      let mean_cos: f32 = (0..samples)
+        .map(|_| cosine_sample_hemisphere(&n, &mut rng).dot(&n))
+        .inspect(|cos| assert!(*cos >= 0.))
+        .sum::<f32>()
+        / samples as f32;
+      assert!((mean_cos - 2. / 3.).abs() < 0.02, "{}", mean_cos);
+    }
+  }
+
+  #[test]
+  fn test_sample_polygon() {
+    let mut rng = rand::thread_rng();
+    assert_eq!(concentric_sample_disk((0.5, 0.5)), (0., 0.));
+    assert_eq!(sample_polygon(6, 0.3, (0.5, 0.5)), (0., 0.));
+    let (sides, rotation) = (5, 0.2);
+    let half_sector = PI / sides as f32;
+    let samples = 20000;
+    let mut in_inner_circle = 0;
+    for _ in 0..samples {
+      let (x, y) = sample_polygon(sides, rotation, (rng.gen(), rng.gen()));
+      let radius = (x * x + y * y).sqrt();
+      let angle = (y.atan2(x) - rotation).rem_euclid(2. * half_sector) - half_sector;
+      // inside the edge, at the apothem's distance from the center
+      assert!(radius * angle.cos() <= half_sector.cos() + 1e-5);
+      if radius < 0.5 {
+        in_inner_circle += 1;
+      }
+    }
+    // uniform: the circle covers its share of the polygon's area
+    let polygon_area = sides as f32 * half_sector.sin() * half_sector.cos();
+    let expected = PI * 0.25 / polygon_area;
+    let ratio = in_inner_circle as f32 / samples as f32;
+    assert!((ratio - expected).abs() < 0.02, "{} {}", ratio, expected);
+  }
+
+  #[test]
+  fn test_sample_patterns() {
+    let mut rng = rand::thread_rng();
+    use SamplePattern::*;
+    for pattern in &[Regular, Jittered, Halton, Sobol] {
+      for pass in 0..3 {
+        let points = pattern.generate(16, pass, 7, &mut rng);
+        assert_eq!(points.len(), 16);
+        // 16 points are enough for one in each cell of a 4x4 grid
+        let mut cells: Vec<u32> = points
+          .iter()
+          .inspect(|(x, y)| assert!((0. ..1.).contains(x) && (0. ..1.).contains(y)))
+          .map(|(x, y)| (x * 4.) as u32 + 4 * (y * 4.) as u32)
+          .collect();
+        cells.sort_unstable();
+        cells.dedup();
+        if let Regular | Jittered = pattern {
+          assert_eq!(cells.len(), 16, "{:?}", pattern);
+        }
+      }
+    }
+    assert_eq!(Regular.generate(1, 0, 0, &mut rng), vec![(0.5, 0.5)]);
+    // unshifted, the first Sobol points each fall into their own quadrant
+    let quadrants: Vec<(bool, bool)> = (0..4u32)
+      .map(|i| {
+        let x = to_unit(i.reverse_bits());
+        let y = to_unit(sobol_second_dimension(i));
+        (x < 0.5, y < 0.5)
+      })
+      .collect();
+    for q in &[(true, true), (true, false), (false, true), (false, false)] {
+      assert!(quadrants.contains(q));
+    }
+  }
+
+  #[test]
+  fn test_filters() {
+    use Filter::*;
+    for filter in &[Box, Tent, Gaussian, Mitchell] {
+      let radius = filter.radius();
+      assert_eq!(filter.weight(radius + 0.01, 0.), 0.);
+      assert!(filter.weight(0., 0.) > 0.);
+      // filters are symmetric and peak at the center
+      assert_eq!(filter.weight(0.3, -0.2), filter.weight(-0.3, 0.2));
+      assert!(filter.weight(0., 0.) >= filter.weight(0.25, 0.));
+    }
+    // Mitchell–Netravali integrates to 1 and goes negative past 1 pixel
+    let steps = 1000;
+    let integral: f32 = (0..steps)
+      .map(|i| Mitchell.weight_1d(-2. + 4. * (i as f32 + 0.5) / steps as f32) * 4. / steps as f32)
+      .sum();
+    assert!((integral - 1.).abs() < 1e-3, "{}", integral);
+    assert!(Mitchell.weight_1d(1.5) < 0.);
+  }
+}
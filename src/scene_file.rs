@@ -0,0 +1,1437 @@
+use crate::aabb::Aabb;
+use crate::camera::{Camera, Equirectangular, Fisheye, Orthographic, Perspective};
+use crate::color::Color;
+use crate::environment::EnvironmentMap;
+use crate::light::{
+  AreaLight, AreaShape, DirectionalLight, EnvironmentLight, Light, PointLight, SpotLight,
+};
+use crate::material::{Bump, Material, MaterialType};
+use crate::noise::Perlin;
+use crate::obj;
+use crate::shapes::mesh::{Mesh, Vertex};
+use crate::shapes::{
+  cone::Cone, cuboid::Cuboid, cylinder::Cylinder, disk::Disk, plane::Plane, shadows::Shadows,
+  sphere::Sphere, torus::Torus, triangle::Triangle, Castable, Shape,
+};
+use crate::sky::{sun_direction, Sky};
+use crate::texture::{
+  Axis, Checker, Gradient, ImageTexture, Marble, Noise, SolidColor, Texture, Wood, WrapMode,
+};
+use crate::volume::{Bounds, Constant, Density, Grid, NoiseDensity, Volume};
+use na::{Point3, Unit, Vector3};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Declarative description of a scene, read from a RON file, e.g.:
+//
+// (
+//   camera: (eye: (0.0, 1.0, 0.0), target: (0.0, 1.0, -1.0), fov: 60.0),
+//   materials: {
+//     "matte": (material_type: Phong(specular_n: 1, k_diffuse: 1.0, k_specular: 0.0)),
+//   },
+//   shapes: [
+//     Sphere(center: (0.0, 1.0, -6.0), radius: 1.0, material: "matte"),
+//   ],
+//   lights: [
+//     Point(position: (-6.0, 10.0, 3.0), color: (1.0, 1.0, 1.0), intensity: 1000.0),
+//   ],
+// )
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneFile {
+  #[serde(default)]
+  pub camera: CameraDescription,
+  #[serde(default)]
+  pub materials: HashMap<String, MaterialDescription>,
+  #[serde(default)]
+  pub shapes: Vec<ShapeDescription>,
+  #[serde(default)]
+  pub lights: Vec<LightDescription>,
+  #[serde(default)]
+  pub volumes: Vec<VolumeDescription>,
+}
+
+type Triple = (f32, f32, f32);
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+  pub eye: Triple,
+  pub target: Triple,
+  #[serde(default = "default_up")]
+  pub up: Triple,
+  // vertical field of view of the perspective projection, in degrees
+  #[serde(default = "default_fov")]
+  pub fov: f32,
+  // depth of field of the perspective projection: radius of the lens,
+  // distance to the plane of focus (by default the target's) and number of
+  // aperture blades (a round aperture below 3)
+  #[serde(default)]
+  pub aperture: f32,
+  #[serde(default)]
+  pub focus_distance: Option<f32>,
+  #[serde(default)]
+  pub blades: u32,
+  #[serde(default)]
+  pub projection: ProjectionDescription,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub enum ProjectionDescription {
+  #[default]
+  Perspective,
+  // `height` of the view, in world units
+  Orthographic {
+    height: f32,
+  },
+  // angle covered by the image circle, in degrees
+  Fisheye {
+    fov: f32,
+  },
+  Equirectangular,
+}
+
+impl CameraDescription {
+  pub fn camera(&self) -> Arc<dyn Camera + Send + Sync> {
+    match self.projection {
+      ProjectionDescription::Perspective => Arc::new(Perspective {
+        fov: self.fov.to_radians(),
+        aperture: self.aperture,
+        focus_distance: self
+          .focus_distance
+          .unwrap_or_else(|| (point(self.target) - point(self.eye)).norm()),
+        blades: self.blades,
+      }),
+      ProjectionDescription::Orthographic { height } => Arc::new(Orthographic { height }),
+      ProjectionDescription::Fisheye { fov } => Arc::new(Fisheye {
+        fov: fov.to_radians(),
+      }),
+      ProjectionDescription::Equirectangular => Arc::new(Equirectangular),
+    }
+  }
+}
+
+fn default_up() -> Triple {
+  (0., 1., 0.)
+}
+
+fn default_fov() -> f32 {
+  90.
+}
+
+impl Default for CameraDescription {
+  fn default() -> Self {
+    CameraDescription {
+      eye: (0., 1., 0.),
+      target: (0., 1., -1.),
+      up: default_up(),
+      fov: default_fov(),
+      aperture: 0.,
+      focus_distance: None,
+      blades: 0,
+      projection: ProjectionDescription::default(),
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialDescription {
+  // by default, white for the light a surface scatters and black for what a
+  // mirror adds to its reflections
+  #[serde(default, deserialize_with = "some_triple")]
+  pub color: Option<Triple>,
+  // replaces `color` when given
+  #[serde(default)]
+  pub texture: Option<TextureDescription>,
+  #[serde(default)]
+  pub bump: Option<BumpDescription>,
+  #[serde(default = "default_albedo")]
+  pub albedo: f32,
+  // light the surface gives off, making the shape a light source, as a
+  // color and a strength it is multiplied by
+  #[serde(default)]
+  pub emission: Triple,
+  #[serde(default = "default_emission_strength")]
+  pub emission_strength: f32,
+  pub material_type: MaterialTypeDescription,
+}
+
+fn default_color() -> Triple {
+  (1., 1., 1.)
+}
+
+// for optional colors written like the others, without `Some`
+fn some_triple<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Triple>, D::Error> {
+  Triple::deserialize(deserializer).map(Some)
+}
+
+fn default_albedo() -> f32 {
+  1.
+}
+
+fn default_emission_strength() -> f32 {
+  1.
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum TextureDescription {
+  // relative to the scene file
+  Image {
+    path: PathBuf,
+    #[serde(default = "default_wrap")]
+    wrap: WrapDescription,
+    // repetitions per unit of texture coordinates
+    #[serde(default = "default_texture_scale")]
+    scale: (f32, f32),
+  },
+  Checker {
+    even: Triple,
+    odd: Triple,
+    #[serde(default = "default_frequency")]
+    frequency: f32,
+  },
+  Gradient {
+    start: Triple,
+    end: Triple,
+    #[serde(default = "default_axis")]
+    axis: AxisDescription,
+  },
+  // solid textures, in object space: `scale` is the size of their features
+  // and `seed` picks one of their variations
+  Noise {
+    #[serde(default)]
+    seed: u64,
+    #[serde(default = "default_noise_scale")]
+    scale: f32,
+    #[serde(default = "default_octaves")]
+    octaves: u32,
+    #[serde(default)]
+    turbulence: bool,
+    low: Triple,
+    high: Triple,
+  },
+  Marble {
+    #[serde(default)]
+    seed: u64,
+    #[serde(default = "default_noise_scale")]
+    scale: f32,
+    #[serde(default = "default_octaves")]
+    octaves: u32,
+    base: Triple,
+    veins: Triple,
+  },
+  Wood {
+    #[serde(default)]
+    seed: u64,
+    #[serde(default = "default_noise_scale")]
+    scale: f32,
+    #[serde(default = "default_octaves")]
+    octaves: u32,
+    #[serde(default = "default_rings")]
+    rings: f32,
+    light: Triple,
+    dark: Triple,
+  },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum BumpDescription {
+  // an image of tangent space normals, read as is rather than as sRGB
+  NormalMap {
+    path: PathBuf,
+    #[serde(default = "default_wrap")]
+    wrap: WrapDescription,
+    #[serde(default = "default_texture_scale")]
+    scale: (f32, f32),
+    #[serde(default = "default_strength")]
+    strength: f32,
+  },
+  HeightMap {
+    texture: TextureDescription,
+    #[serde(default = "default_strength")]
+    strength: f32,
+  },
+}
+
+fn default_strength() -> f32 {
+  1.
+}
+
+#[derive(Debug, Deserialize)]
+pub enum WrapDescription {
+  Repeat,
+  Mirror,
+  Clamp,
+}
+
+#[derive(Debug, Deserialize)]
+pub enum AxisDescription {
+  U,
+  V,
+}
+
+fn default_wrap() -> WrapDescription {
+  WrapDescription::Repeat
+}
+
+fn default_texture_scale() -> (f32, f32) {
+  (1., 1.)
+}
+
+fn default_frequency() -> f32 {
+  1.
+}
+
+fn default_axis() -> AxisDescription {
+  AxisDescription::V
+}
+
+fn default_noise_scale() -> f32 {
+  1.
+}
+
+fn default_octaves() -> u32 {
+  6
+}
+
+fn default_rings() -> f32 {
+  4.
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum MaterialTypeDescription {
+  Reflection {
+    reflectivity: f32,
+    #[serde(default)]
+    roughness: f32,
+  },
+  Refraction {
+    refractive_index: f32,
+    #[serde(default)]
+    roughness: f32,
+    // linear, per unit of distance: light of the complementary colors
+    // fades first as it goes through
+    #[serde(default)]
+    absorption: Triple,
+  },
+  Phong {
+    k_specular: f32,
+    k_diffuse: f32,
+    specular_n: i32,
+  },
+  Pbr {
+    #[serde(default)]
+    metallic: f32,
+    #[serde(default = "default_roughness")]
+    roughness: f32,
+    #[serde(default = "default_pbr_specular")]
+    specular: f32,
+  },
+  // `second` where `mask` is white, `first` where it is black, or a
+  // constant blend by `weight` without a mask
+  Mix {
+    first: Box<MaterialDescription>,
+    second: Box<MaterialDescription>,
+    #[serde(default)]
+    mask: Option<TextureDescription>,
+    #[serde(default = "default_mix_weight")]
+    weight: f32,
+  },
+  ClearCoat {
+    base: Box<MaterialDescription>,
+    #[serde(default = "default_coat_refractive_index")]
+    refractive_index: f32,
+    #[serde(default)]
+    roughness: f32,
+    #[serde(default = "default_coat_weight")]
+    weight: f32,
+  },
+}
+
+fn default_mix_weight() -> f32 {
+  0.5
+}
+
+fn default_coat_refractive_index() -> f32 {
+  1.5
+}
+
+fn default_coat_weight() -> f32 {
+  1.
+}
+
+fn default_roughness() -> f32 {
+  0.5
+}
+
+fn default_pbr_specular() -> f32 {
+  0.5
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum ShapeDescription {
+  Sphere {
+    center: Triple,
+    radius: f32,
+    material: String,
+  },
+  Plane {
+    normal: Triple,
+    center: Triple,
+    #[serde(default)]
+    size: (Option<f32>, Option<f32>),
+    #[serde(default)]
+    rotation: Triple,
+    material: String,
+  },
+  Triangle {
+    vertices: (Triple, Triple, Triple),
+    material: String,
+  },
+  // `size` being its full extents along its own axes
+  Box {
+    center: Triple,
+    size: Triple,
+    #[serde(default)]
+    rotation: Triple,
+    material: String,
+  },
+  // closed at both ends, standing along y unless rotated
+  Cylinder {
+    center: Triple,
+    radius: f32,
+    height: f32,
+    #[serde(default)]
+    rotation: Triple,
+    material: String,
+  },
+  // closed, its base at `center` and its apex `height` above it
+  Cone {
+    center: Triple,
+    radius: f32,
+    height: f32,
+    #[serde(default)]
+    rotation: Triple,
+    material: String,
+  },
+  // facing up unless rotated
+  Disk {
+    center: Triple,
+    radius: f32,
+    #[serde(default)]
+    rotation: Triple,
+    material: String,
+  },
+  // lying flat unless rotated
+  Torus {
+    center: Triple,
+    major_radius: f32,
+    minor_radius: f32,
+    #[serde(default)]
+    rotation: Triple,
+    material: String,
+  },
+  // Wavefront OBJ file, relative to the scene file. Faces use the materials
+  // of its MTL libraries unless `material` overrides them all.
+  Mesh {
+    path: PathBuf,
+    #[serde(default)]
+    position: Triple,
+    #[serde(default)]
+    rotation: Triple,
+    #[serde(default = "default_scale")]
+    scale: f32,
+    #[serde(default)]
+    material: Option<String>,
+  },
+  // `shape` casting and receiving shadows or not, whatever its material
+  Shadows {
+    #[serde(default = "default_true")]
+    cast: bool,
+    #[serde(default = "default_true")]
+    receive: bool,
+    shape: Box<ShapeDescription>,
+  },
+}
+
+fn default_true() -> bool {
+  true
+}
+
+fn default_scale() -> f32 {
+  1.
+}
+
+// for mesh faces without a material
+fn default_material() -> Material {
+  Material {
+    color: Arc::new(SolidColor(Color::new(0.8, 0.8, 0.8))),
+    albedo: 1.,
+    material_type: MaterialType::Phong {
+      k_specular: 0.,
+      k_diffuse: 1.,
+      specular_n: 1,
+    },
+    bump: None,
+    emission: Color::zero(),
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum LightDescription {
+  Point {
+    position: Triple,
+    color: Triple,
+    intensity: f32,
+  },
+  // parallel rays going along `direction`, `intensity` being the light
+  // received by a surface facing them
+  Directional {
+    direction: Triple,
+    color: Triple,
+    intensity: f32,
+  },
+  // a point light shining along `direction`, fading out from `inner_angle`
+  // to `outer_angle`, in degrees; a `falloff` above 1 sharpens the fade
+  Spot {
+    position: Triple,
+    direction: Triple,
+    inner_angle: f32,
+    outer_angle: f32,
+    #[serde(default = "default_spot_falloff")]
+    falloff: f32,
+    color: Triple,
+    intensity: f32,
+  },
+  // uniform light from every direction, seen by rays leaving the scene
+  Environment {
+    color: Triple,
+    intensity: f32,
+    #[serde(default = "default_light_samples")]
+    samples: u32,
+  },
+  // an equirectangular image of the surroundings, relative to the scene
+  // file: Radiance .hdr and OpenEXR .exr images are linear, others sRGB.
+  // `rotation` turns it around the y axis, in degrees, and `intensity`
+  // scales its light.
+  EnvironmentMap {
+    path: PathBuf,
+    #[serde(default)]
+    rotation: f32,
+    #[serde(default = "default_environment_intensity")]
+    intensity: f32,
+    #[serde(default = "default_light_samples")]
+    samples: u32,
+  },
+  // a daylight sky lit by its sun, `sun_elevation` degrees above the
+  // horizon and `sun_azimuth` degrees from -z towards +x. `turbidity` goes
+  // from 2 for a clear sky to 10 for a hazy one; `intensity` scales the
+  // light of the sky and `sun_intensity` is the sun's outside of the
+  // atmosphere, on a surface facing it.
+  Sky {
+    sun_elevation: f32,
+    #[serde(default)]
+    sun_azimuth: f32,
+    #[serde(default = "default_turbidity")]
+    turbidity: f32,
+    #[serde(default = "default_environment_intensity")]
+    intensity: f32,
+    #[serde(default = "default_sun_intensity")]
+    sun_intensity: f32,
+    #[serde(default = "default_light_samples")]
+    samples: u32,
+  },
+  // area lights, lit on the side of `normal`, or of u × v for rectangles
+  // spanning center ± u ± v. `samples` is the number of shadow rays sent to
+  // them from each point lit: more give smoother penumbrae.
+  Rectangle {
+    center: Triple,
+    u: Triple,
+    v: Triple,
+    color: Triple,
+    intensity: f32,
+    #[serde(default = "default_light_samples")]
+    samples: u32,
+  },
+  Disk {
+    center: Triple,
+    normal: Triple,
+    radius: f32,
+    color: Triple,
+    intensity: f32,
+    #[serde(default = "default_light_samples")]
+    samples: u32,
+  },
+  Sphere {
+    center: Triple,
+    radius: f32,
+    color: Triple,
+    intensity: f32,
+    #[serde(default = "default_light_samples")]
+    samples: u32,
+  },
+}
+
+fn default_light_samples() -> u32 {
+  16
+}
+
+fn default_spot_falloff() -> f32 {
+  1.
+}
+
+fn default_environment_intensity() -> f32 {
+  1.
+}
+
+fn default_turbidity() -> f32 {
+  3.
+}
+
+fn default_sun_intensity() -> f32 {
+  3.
+}
+
+// Fog or smoke, filling `bounds`, or the whole scene without them.
+// `absorption` and `scattering` are per unit of distance where the density
+// is 1, and `color` tints the scattered light. `anisotropy` goes from -1 for
+// light scattered back to 1 for light going on, 0 scattering it evenly.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VolumeDescription {
+  #[serde(default)]
+  pub bounds: Option<BoundsDescription>,
+  pub absorption: f32,
+  pub scattering: f32,
+  #[serde(default = "default_color")]
+  pub color: Triple,
+  #[serde(default)]
+  pub anisotropy: f32,
+  // 1 everywhere without one
+  #[serde(default)]
+  pub density: Option<DensityDescription>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum BoundsDescription {
+  Sphere { center: Triple, radius: f32 },
+  Box { min: Triple, max: Triple },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum DensityDescription {
+  Constant(f32),
+  // puffs of noise between 0 and 1, with gaps where it is below `threshold`
+  Noise {
+    #[serde(default)]
+    seed: u64,
+    #[serde(default = "default_noise_scale")]
+    scale: f32,
+    #[serde(default = "default_octaves")]
+    octaves: u32,
+    #[serde(default)]
+    threshold: f32,
+  },
+  // `size` values, x varying fastest, at the corners of cells filling the
+  // box from `min` to `max`; none outside
+  Grid {
+    min: Triple,
+    max: Triple,
+    size: (usize, usize, usize),
+    values: Vec<f32>,
+  },
+}
+
+impl VolumeDescription {
+  fn volume(&self) -> Result<Volume, String> {
+    let bounds = match self.bounds {
+      None => Bounds::Everywhere,
+      Some(BoundsDescription::Sphere { center, radius }) => Bounds::Sphere {
+        center: point(center),
+        radius,
+      },
+      Some(BoundsDescription::Box { min, max }) => Bounds::Box(Aabb::new(point(min), point(max))),
+    };
+    let density: Arc<dyn Density> = match &self.density {
+      None => Arc::new(Constant(1.)),
+      Some(DensityDescription::Constant(density)) => Arc::new(Constant(density.max(0.))),
+      Some(DensityDescription::Noise {
+        seed,
+        scale,
+        octaves,
+        threshold,
+      }) => Arc::new(NoiseDensity {
+        perlin: Perlin::new(*seed),
+        scale: *scale,
+        octaves: *octaves,
+        threshold: threshold.clamp(0., 1.),
+      }),
+      Some(DensityDescription::Grid {
+        min,
+        max,
+        size,
+        values,
+      }) => Arc::new(Grid::new(
+        Aabb::new(point(*min), point(*max)),
+        *size,
+        values.clone(),
+      )?),
+    };
+    // rays leaving a scene without bounded shapes would go through it forever
+    if self.bounds.is_none() && density.constant().is_none() && density.bounds().is_none() {
+      return Err("noise densities need bounds".to_string());
+    }
+    Ok(Volume {
+      bounds,
+      absorption: self.absorption.max(0.),
+      scattering: self.scattering.max(0.),
+      color: color(self.color),
+      // the phase function is a spike at ±1
+      anisotropy: self.anisotropy.clamp(-0.99, 0.99),
+      density,
+    })
+  }
+}
+
+// everything a scene file turns into; the world borrows its shapes from here
+pub struct LoadedScene {
+  pub camera: CameraDescription,
+  pub shapes: Vec<Box<dyn Shape + Sync>>,
+  pub lights: Vec<Box<dyn Light>>,
+  pub volumes: Vec<Volume>,
+}
+
+pub fn point(p: Triple) -> Point3<f32> {
+  Point3::new(p.0, p.1, p.2)
+}
+
+pub fn vector(v: Triple) -> Vector3<f32> {
+  Vector3::new(v.0, v.1, v.2)
+}
+
+// colors are written the way they are picked, sRGB encoded
+fn color((r, g, b): Triple) -> Color {
+  Color::from_srgb(r, g, b)
+}
+
+impl From<&WrapDescription> for WrapMode {
+  fn from(wrap: &WrapDescription) -> WrapMode {
+    match wrap {
+      WrapDescription::Repeat => WrapMode::Repeat,
+      WrapDescription::Mirror => WrapMode::Mirror,
+      WrapDescription::Clamp => WrapMode::Clamp,
+    }
+  }
+}
+
+impl BumpDescription {
+  fn bump(&self, directory: &Path) -> Result<Bump, String> {
+    Ok(match self {
+      BumpDescription::NormalMap {
+        path,
+        wrap,
+        scale,
+        strength,
+      } => Bump::NormalMap {
+        texture: Arc::new(ImageTexture::load(
+          &directory.join(path),
+          wrap.into(),
+          *scale,
+          false,
+        )?),
+        strength: *strength,
+      },
+      BumpDescription::HeightMap { texture, strength } => Bump::HeightMap {
+        texture: texture.texture(directory)?,
+        strength: *strength,
+      },
+    })
+  }
+}
+
+impl TextureDescription {
+  fn texture(&self, directory: &Path) -> Result<Arc<dyn Texture>, String> {
+    Ok(match self {
+      TextureDescription::Image { path, wrap, scale } => Arc::new(ImageTexture::load(
+        &directory.join(path),
+        wrap.into(),
+        *scale,
+        true,
+      )?),
+      TextureDescription::Checker {
+        even,
+        odd,
+        frequency,
+      } => Arc::new(Checker {
+        even: color(*even),
+        odd: color(*odd),
+        frequency: *frequency,
+      }),
+      TextureDescription::Gradient { start, end, axis } => Arc::new(Gradient {
+        start: color(*start),
+        end: color(*end),
+        axis: match axis {
+          AxisDescription::U => Axis::U,
+          AxisDescription::V => Axis::V,
+        },
+      }),
+      TextureDescription::Noise {
+        seed,
+        scale,
+        octaves,
+        turbulence,
+        low,
+        high,
+      } => Arc::new(Noise {
+        perlin: Perlin::new(*seed),
+        scale: *scale,
+        octaves: *octaves,
+        turbulence: *turbulence,
+        low: color(*low),
+        high: color(*high),
+      }),
+      TextureDescription::Marble {
+        seed,
+        scale,
+        octaves,
+        base,
+        veins,
+      } => Arc::new(Marble {
+        perlin: Perlin::new(*seed),
+        scale: *scale,
+        octaves: *octaves,
+        base: color(*base),
+        veins: color(*veins),
+      }),
+      TextureDescription::Wood {
+        seed,
+        scale,
+        octaves,
+        rings,
+        light,
+        dark,
+      } => Arc::new(Wood {
+        perlin: Perlin::new(*seed),
+        scale: *scale,
+        octaves: *octaves,
+        rings: *rings,
+        light: color(*light),
+        dark: color(*dark),
+      }),
+    })
+  }
+}
+
+impl MaterialDescription {
+  // `directory` is where texture paths are relative to
+  fn material(&self, directory: &Path) -> Result<Material, String> {
+    use MaterialTypeDescription::*;
+    Ok(Material {
+      color: match &self.texture {
+        Some(texture) => texture.texture(directory)?,
+        None => Arc::new(SolidColor(match (self.color, &self.material_type) {
+          (Some(triple), _) => color(triple),
+          (None, Reflection { .. }) => Color::zero(),
+          (None, _) => color(default_color()),
+        })),
+      },
+      albedo: self.albedo,
+      material_type: match self.material_type {
+        Reflection {
+          reflectivity,
+          roughness,
+        } => MaterialType::Reflection {
+          reflectivity,
+          roughness: roughness.clamp(0., 1.),
+        },
+        Refraction {
+          refractive_index,
+          roughness,
+          absorption: (r, g, b),
+        } => MaterialType::Refraction {
+          refractive_index,
+          roughness: roughness.clamp(0., 1.),
+          absorption: Color::new(r, g, b),
+        },
+        Mix {
+          ref first,
+          ref second,
+          ref mask,
+          weight,
+        } => MaterialType::Mix {
+          first: Arc::new(
+            first
+              .material(directory)
+              .map_err(|e| format!("first: {}", e))?,
+          ),
+          second: Arc::new(
+            second
+              .material(directory)
+              .map_err(|e| format!("second: {}", e))?,
+          ),
+          mask: match mask {
+            Some(mask) => mask.texture(directory)?,
+            None => Arc::new(SolidColor(Color::new(weight, weight, weight))),
+          },
+        },
+        ClearCoat {
+          ref base,
+          refractive_index,
+          roughness,
+          weight,
+        } => MaterialType::ClearCoat {
+          base: Arc::new(
+            base
+              .material(directory)
+              .map_err(|e| format!("base: {}", e))?,
+          ),
+          refractive_index,
+          roughness: roughness.clamp(0., 1.),
+          weight: weight.clamp(0., 1.),
+        },
+        Phong {
+          k_specular,
+          k_diffuse,
+          specular_n,
+        } => MaterialType::Phong {
+          k_specular,
+          k_diffuse,
+          specular_n,
+        },
+        Pbr {
+          metallic,
+          roughness,
+          specular,
+        } => MaterialType::Pbr {
+          metallic: metallic.clamp(0., 1.),
+          roughness: roughness.clamp(0., 1.),
+          specular,
+        },
+      },
+      bump: match &self.bump {
+        Some(bump) => Some(bump.bump(directory)?),
+        None => None,
+      },
+      emission: color(self.emission) * self.emission_strength.max(0.),
+    })
+  }
+}
+
+impl ShapeDescription {
+  // adds the shapes described to `shapes`
+  fn load(
+    &self,
+    directory: &Path,
+    find_material: &dyn Fn(&str) -> Result<Material, String>,
+    shapes: &mut Vec<Box<dyn Shape + Sync>>,
+  ) -> Result<(), String> {
+    match self {
+      ShapeDescription::Sphere {
+        center,
+        radius,
+        material,
+      } => shapes.push(Box::new(Sphere::new(
+        point(*center),
+        *radius,
+        find_material(material)?,
+      ))),
+      ShapeDescription::Plane {
+        normal,
+        center,
+        size,
+        rotation,
+        material,
+      } => {
+        let material = find_material(material)?;
+        let glows = material.emission.max_component() > 0.;
+        let plane = Plane::new(
+          Unit::new_normalize(vector(*normal)),
+          point(*center),
+          *size,
+          vector(*rotation),
+          material,
+        );
+        // emissive shapes are lights sampled over their surface, which
+        // planes without bounds don't have
+        if glows && plane.emitters().is_empty() {
+          return Err("emissive planes need to be bounded, by a size on both sides".to_string());
+        }
+        shapes.push(Box::new(plane));
+      }
+      ShapeDescription::Triangle {
+        vertices: (a, b, c),
+        material,
+      } => shapes.push(Box::new(Triangle::new(
+        [point(*a), point(*b), point(*c)],
+        find_material(material)?,
+      ))),
+      ShapeDescription::Box {
+        center,
+        size,
+        rotation,
+        material,
+      } => shapes.push(Box::new(Cuboid::new(
+        point(*center),
+        vector(*size) / 2.,
+        vector(*rotation),
+        find_material(material)?,
+      ))),
+      ShapeDescription::Cylinder {
+        center,
+        radius,
+        height,
+        rotation,
+        material,
+      } => shapes.push(Box::new(Cylinder::new(
+        point(*center),
+        *radius,
+        *height,
+        vector(*rotation),
+        find_material(material)?,
+      ))),
+      ShapeDescription::Cone {
+        center,
+        radius,
+        height,
+        rotation,
+        material,
+      } => shapes.push(Box::new(Cone::new(
+        point(*center),
+        *radius,
+        *height,
+        vector(*rotation),
+        find_material(material)?,
+      ))),
+      ShapeDescription::Disk {
+        center,
+        radius,
+        rotation,
+        material,
+      } => shapes.push(Box::new(Disk::new(
+        point(*center),
+        *radius,
+        vector(*rotation),
+        find_material(material)?,
+      ))),
+      ShapeDescription::Torus {
+        center,
+        major_radius,
+        minor_radius,
+        rotation,
+        material,
+      } => shapes.push(Box::new(Torus::new(
+        point(*center),
+        *major_radius,
+        *minor_radius,
+        vector(*rotation),
+        find_material(material)?,
+      ))),
+      ShapeDescription::Mesh {
+        path,
+        position,
+        rotation,
+        scale,
+        material,
+      } => {
+        let obj = obj::load(&directory.join(path), default_material())?;
+        let materials = match material {
+          Some(name) => vec![find_material(name)?],
+          None => obj.materials,
+        };
+        for mesh in obj.meshes {
+          let vertices = mesh
+            .vertices
+            .into_iter()
+            .map(|vertex| Vertex {
+              position: vertex.position * *scale,
+              ..vertex
+            })
+            .collect();
+          let face_materials = match material {
+            Some(_) => vec![0; mesh.face_materials.len()],
+            None => mesh.face_materials,
+          };
+          shapes.push(Box::new(Mesh::new(
+            vertices,
+            mesh.triangles,
+            materials.clone(),
+            face_materials,
+            point(*position),
+            vector(*rotation),
+          )));
+        }
+      }
+      ShapeDescription::Shadows {
+        cast,
+        receive,
+        shape,
+      } => {
+        let mut inner = vec![];
+        shape.load(directory, find_material, &mut inner)?;
+        for shape in inner {
+          shapes.push(Box::new(Shadows {
+            shape,
+            cast: *cast,
+            receive: *receive,
+          }));
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+impl SceneFile {
+  pub fn parse(source: &str) -> Result<SceneFile, String> {
+    ron::from_str(source).map_err(|e| e.to_string())
+  }
+
+  // `directory` is where paths in the file are relative to
+  pub fn into_scene(self, directory: &Path) -> Result<LoadedScene, String> {
+    let mut materials: HashMap<&str, Material> = HashMap::new();
+    for (name, description) in self.materials.iter() {
+      let material = description
+        .material(directory)
+        .map_err(|e| format!("materials[\"{}\"]: {}", name, e))?;
+      materials.insert(name.as_str(), material);
+    }
+    let find_material = |name: &str| {
+      materials
+        .get(name)
+        .cloned()
+        .ok_or_else(|| format!("unknown material \"{}\"", name))
+    };
+
+    let mut shapes: Vec<Box<dyn Shape + Sync>> = vec![];
+    for (index, shape) in self.shapes.iter().enumerate() {
+      shape
+        .load(directory, &find_material, &mut shapes)
+        .map_err(|e| format!("shapes[{}]: {}", index, e))?;
+    }
+
+    let lights = self
+      .lights
+      .iter()
+      .enumerate()
+      .map(|(index, light)| -> Result<Box<dyn Light>, String> {
+        let light: Box<dyn Light> = match light {
+          LightDescription::Point {
+            position,
+            color: light_color,
+            intensity,
+          } => Box::new(PointLight {
+            position: point(*position),
+            color: color(*light_color),
+            intensity: *intensity,
+          }),
+          LightDescription::Directional {
+            direction,
+            color: light_color,
+            intensity,
+          } => Box::new(DirectionalLight {
+            direction: Unit::new_normalize(vector(*direction)),
+            color: color(*light_color),
+            intensity: *intensity,
+          }),
+          LightDescription::Spot {
+            position,
+            direction,
+            inner_angle,
+            outer_angle,
+            falloff,
+            color: light_color,
+            intensity,
+          } => Box::new(SpotLight {
+            position: point(*position),
+            direction: Unit::new_normalize(vector(*direction)),
+            inner_angle: inner_angle.min(*outer_angle).to_radians(),
+            outer_angle: outer_angle.to_radians(),
+            falloff: *falloff,
+            color: color(*light_color),
+            intensity: *intensity,
+          }),
+          LightDescription::Environment {
+            color: light_color,
+            intensity,
+            samples,
+          } => Box::new(EnvironmentLight {
+            color: color(*light_color),
+            intensity: *intensity,
+            samples: *samples,
+          }),
+          LightDescription::EnvironmentMap {
+            path,
+            rotation,
+            intensity,
+            samples,
+          } => Box::new(
+            EnvironmentMap::load(
+              &directory.join(path),
+              rotation.to_radians(),
+              *intensity,
+              *samples,
+            )
+            .map_err(|e| format!("lights[{}]: {}", index, e))?,
+          ),
+          LightDescription::Sky {
+            sun_elevation,
+            sun_azimuth,
+            turbidity,
+            intensity,
+            sun_intensity,
+            samples,
+          } => Box::new(Sky::new(
+            sun_direction(sun_elevation.to_radians(), sun_azimuth.to_radians()),
+            // beyond what the model was fitted to
+            turbidity.clamp(1.7, 10.),
+            *intensity,
+            *sun_intensity,
+            *samples,
+          )),
+          LightDescription::Rectangle {
+            center,
+            u,
+            v,
+            color: light_color,
+            intensity,
+            samples,
+          } => Box::new(AreaLight {
+            shape: AreaShape::Rectangle {
+              center: point(*center),
+              u: vector(*u),
+              v: vector(*v),
+            },
+            color: color(*light_color),
+            intensity: *intensity,
+            samples: *samples,
+          }),
+          LightDescription::Disk {
+            center,
+            normal,
+            radius,
+            color: light_color,
+            intensity,
+            samples,
+          } => Box::new(AreaLight {
+            shape: AreaShape::Disk {
+              center: point(*center),
+              normal: Unit::new_normalize(vector(*normal)),
+              radius: *radius,
+            },
+            color: color(*light_color),
+            intensity: *intensity,
+            samples: *samples,
+          }),
+          LightDescription::Sphere {
+            center,
+            radius,
+            color: light_color,
+            intensity,
+            samples,
+          } => Box::new(AreaLight {
+            shape: AreaShape::Sphere {
+              center: point(*center),
+              radius: *radius,
+            },
+            color: color(*light_color),
+            intensity: *intensity,
+            samples: *samples,
+          }),
+        };
+        Ok(light)
+      })
+      .collect::<Result<_, _>>()?;
+
+    let volumes = self
+      .volumes
+      .iter()
+      .enumerate()
+      .map(|(index, volume)| {
+        volume
+          .volume()
+          .map_err(|e| format!("volumes[{}]: {}", index, e))
+      })
+      .collect::<Result<_, _>>()?;
+
+    Ok(LoadedScene {
+      camera: self.camera,
+      shapes,
+      lights,
+      volumes,
+    })
+  }
+}
+
+pub fn load(path: &Path) -> Result<LoadedScene, String> {
+  let source =
+    fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+  let directory = path.parent().unwrap_or_else(|| Path::new(""));
+  SceneFile::parse(&source)
+    .and_then(|scene| scene.into_scene(directory))
+    .map_err(|e| format!("{}:{}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ray::Ray;
+
+  #[test]
+  fn test_errors_point_at_location() {
+    let error = SceneFile::parse(
+      "(
+  shapes: [
+    Sphere(center: (0.0, 0.0, 0.0), radiu: 1.0, material: \"a\"),
+  ],
+)",
+    )
+    .unwrap_err();
+    assert!(error.starts_with("3:"), "{}", error);
+    assert!(error.contains("radiu"), "{}", error);
+  }
+
+  #[test]
+  fn test_unknown_material() {
+    let error = SceneFile::parse(
+      "(
+  materials: {
+    \"matte\": (material_type: Phong(specular_n: 1, k_diffuse: 1.0, k_specular: 0.0)),
+  },
+  shapes: [
+    Sphere(center: (0.0, 0.0, 0.0), radius: 1.0, material: \"matte\"),
+    Sphere(center: (0.0, 0.0, 0.0), radius: 1.0, material: \"glass\"),
+  ],
+)",
+    )
+    .unwrap()
+    .into_scene(Path::new(""))
+    .err()
+    .unwrap();
+    assert_eq!(error, "shapes[1]: unknown material \"glass\"");
+  }
+
+  #[test]
+  fn test_emissive_planes_need_bounds() {
+    let scene = |size: &str| {
+      SceneFile::parse(&format!(
+        "(
+  materials: {{
+    \"lamp\": (material_type: Pbr(), emission: (1.0, 1.0, 1.0)),
+  }},
+  shapes: [
+    Plane(normal: (0.0, -1.0, 0.0), center: (0.0, 3.0, 0.0), size: {}, material: \"lamp\"),
+  ],
+)",
+        size
+      ))
+      .unwrap()
+      .into_scene(Path::new(""))
+    };
+    assert!(scene("(Some(1.0), Some(1.0))").is_ok());
+    assert_eq!(
+      scene("(Some(1.0), None)").err().unwrap(),
+      "shapes[0]: emissive planes need to be bounded, by a size on both sides"
+    );
+  }
+
+  #[test]
+  fn test_layered_material_errors() {
+    let error = SceneFile::parse(
+      "(
+  materials: {
+    \"paint\": (material_type: ClearCoat(base: (
+      material_type: Mix(
+        first: (material_type: Pbr()),
+        second: (texture: Some(Image(path: \"missing.png\")), material_type: Pbr()),
+      ),
+    ))),
+  },
+)",
+    )
+    .unwrap()
+    .into_scene(Path::new(""))
+    .err()
+    .unwrap();
+    assert!(
+      error.starts_with("materials[\"paint\"]: base: second: could not read"),
+      "{}",
+      error
+    );
+  }
+
+  #[test]
+  fn test_default_colors() {
+    let scene = SceneFile::parse(
+      "(
+  materials: {
+    \"matte\": (material_type: Phong(specular_n: 1, k_diffuse: 1.0, k_specular: 0.0)),
+    \"mirror\": (material_type: Reflection(reflectivity: 0.8)),
+    \"red\": (color: (1.0, 0.0, 0.0), material_type: Reflection(reflectivity: 0.8)),
+  },
+  shapes: [
+    Sphere(center: (0.0, 0.0, 0.0), radius: 1.0, material: \"matte\"),
+    Sphere(center: (0.0, 0.0, 0.0), radius: 1.0, material: \"mirror\"),
+    Sphere(center: (0.0, 0.0, 0.0), radius: 1.0, material: \"red\"),
+  ],
+)",
+    )
+    .unwrap()
+    .into_scene(Path::new(""))
+    .unwrap();
+    let ray = Ray {
+      origin: Point3::new(0., 0., 5.),
+      direction: -Vector3::z_axis(),
+    };
+    let colors: Vec<Color> = scene
+      .shapes
+      .iter()
+      .map(|shape| shape.cast_ray(&ray).unwrap().surface_color())
+      .collect();
+    // a mirror without a color adds nothing to what it reflects
+    assert_eq!(
+      colors,
+      vec![
+        Color::new(1., 1., 1.),
+        Color::zero(),
+        Color::new(1., 0., 0.)
+      ]
+    );
+  }
+
+  #[test]
+  fn test_shadow_settings() {
+    let scene = SceneFile::parse(
+      "(
+  materials: {
+    \"glass\": (material_type: Refraction(refractive_index: 1.5)),
+  },
+  shapes: [
+    Sphere(center: (0.0, 0.0, 0.0), radius: 1.0, material: \"glass\"),
+    Shadows(cast: false, shape: Sphere(center: (0.0, 0.0, 0.0), radius: 1.0, material: \"glass\")),
+    Shadows(receive: false, shape: Plane(normal: (0.0, 1.0, 0.0), center: (0.0, 0.0, 0.0), material: \"glass\")),
+  ],
+)",
+    )
+    .unwrap()
+    .into_scene(Path::new(""))
+    .unwrap();
+    let settings: Vec<(bool, bool)> = scene
+      .shapes
+      .iter()
+      .map(|shape| (shape.is_shadow_casting(), shape.receives_shadows()))
+      .collect();
+    // glass casts shadows too, lighter ones
+    assert_eq!(settings, vec![(true, true), (false, true), (true, false)]);
+  }
+}
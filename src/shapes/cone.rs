@@ -0,0 +1,169 @@
+use super::{super::ray::Ray, CastInfo};
+use super::{find_roots_quadratic, Castable};
+use crate::aabb::Aabb;
+use crate::light::{AreaLight, AreaShape};
+use crate::{material::Material, shapes::Shape};
+use na::{Isometry3, Point2, Point3, Unit, Vector3};
+use std::f32::consts::PI;
+
+// closed by a disk at its base, around its center, and pointing up `height`
+// above it unless rotated
+#[derive(Debug, Clone)]
+pub struct Cone {
+  radius: f32,
+  height: f32,
+  material: Material,
+
+  world_to_object: Isometry3<f32>,
+  object_to_world: Isometry3<f32>,
+}
+
+impl Cone {
+  pub fn new(
+    center: Point3<f32>,
+    radius: f32,
+    height: f32,
+    rotation: Vector3<f32>,
+    material: Material,
+  ) -> Cone {
+    let model_matrix = Isometry3::new(center.coords, rotation);
+    Cone {
+      radius,
+      height,
+      material,
+      object_to_world: model_matrix,
+      world_to_object: model_matrix.inverse(),
+    }
+  }
+}
+
+impl Castable for Cone {
+  fn cast_ray(&self, world_ray: &Ray) -> Option<CastInfo<'_>> {
+    let ray = world_ray.apply_isometry(self.world_to_object);
+    let (o, d) = (ray.origin, ray.direction.into_inner());
+    let (r, h) = (self.radius, self.height);
+    // distance to the nearest surface in front of the ray, and whether it
+    // is the base
+    let mut nearest: Option<(f32, bool)> = None;
+    let mut consider = |t: f32, base: bool| {
+      if t > 0. && nearest.is_none_or(|(nearest_t, _)| t < nearest_t) {
+        nearest = Some((t, base));
+      }
+    };
+
+    // the side, where x² + z² = (k (h - y))², below the apex
+    let k2 = (r / h) * (r / h);
+    let below = h - o.y;
+    let a = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+    let b = 2. * (o.x * d.x + o.z * d.z + k2 * below * d.y);
+    let c = o.x * o.x + o.z * o.z - k2 * below * below;
+    let on_side = |t: f32| (0. ..=h).contains(&(o.y + t * d.y));
+    if a.abs() < 1e-9 {
+      // parallel to the side, crossing it once
+      let t = -c / b;
+      if t.is_finite() && on_side(t) {
+        consider(t, false);
+      }
+    } else if let Some((t0, t1)) = find_roots_quadratic(a, b, c) {
+      for t in [t0, t1] {
+        if on_side(t) {
+          consider(t, false);
+        }
+      }
+    }
+    let t = -o.y / d.y;
+    let (x, z) = (o.x + t * d.x, o.z + t * d.z);
+    if t.is_finite() && x * x + z * z <= r * r {
+      consider(t, true);
+    }
+
+    let (t, base) = nearest?;
+    let point_hit = o + d * t;
+    let p = point_hit.coords;
+    let (normal, tangent, uv) = if base {
+      (
+        -Vector3::y(),
+        Vector3::x(),
+        Point2::new(0.5 + p.x / (2. * r), 0.5 - p.z / (2. * r)),
+      )
+    } else {
+      // around the axis like the longitude of a sphere, then up to the apex
+      let normal = Vector3::new(p.x, k2 * (h - p.y), p.z);
+      let tangent = Vector3::new(p.z, 0., -p.x);
+      let at_apex = normal.norm_squared() < 1e-12;
+      (
+        if at_apex { Vector3::y() } else { normal },
+        if at_apex { Vector3::x() } else { tangent },
+        Point2::new(0.5 + p.x.atan2(p.z) / (2. * PI), p.y / h),
+      )
+    };
+    let normal = Unit::new_normalize(normal);
+    Some(
+      CastInfo {
+        normal,
+        shading_normal: normal,
+        tangent: Unit::new_normalize(tangent),
+        pointing_to_viewer: Unit::new_normalize(ray.origin - point_hit),
+        point_hit,
+        object_point: point_hit,
+        distance: t,
+        casted: self,
+        material: &self.material,
+        uv,
+      }
+      .with_bump()
+      .apply_isometry(self.object_to_world),
+    )
+  }
+
+  fn bounding_box(&self) -> Option<Aabb> {
+    let r = self.radius;
+    Some(
+      Aabb::new(Point3::new(-r, 0., -r), Point3::new(r, self.height, r))
+        .transform(&self.object_to_world),
+    )
+  }
+
+  fn emitters(&self) -> Vec<AreaLight> {
+    if self.material.emission.max_component() <= 0. {
+      return vec![];
+    }
+    let shape = AreaShape::Cone {
+      to_world: self.object_to_world,
+      radius: self.radius,
+      height: self.height,
+    };
+    vec![AreaLight::emitting(shape, self.material.emission)]
+  }
+}
+
+impl Shape for Cone {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::shapes::test_helpers::{material, ray};
+
+  #[test]
+  fn test_cast() {
+    let cone = Cone::new(Point3::origin(), 1., 2., Vector3::zeros(), material());
+
+    // halfway up the side, facing out and up, then the base from below
+    let info = cone
+      .cast_ray(&ray(Point3::new(-3., 1., 0.), Vector3::x()))
+      .unwrap();
+    assert!((info.point_hit - Point3::new(-0.5, 1., 0.)).norm() < 1e-5);
+    let expected = Vector3::new(-2., 1., 0.).normalize();
+    assert!((info.normal.into_inner() - expected).norm() < 1e-5);
+    assert!((info.uv.y - 0.5).abs() < 1e-5);
+    let info = cone
+      .cast_ray(&ray(Point3::new(0.5, -3., 0.), Vector3::y()))
+      .unwrap();
+    assert!(info.point_hit.y.abs() < 1e-5);
+    assert!((info.normal.into_inner() + Vector3::y()).norm() < 1e-5);
+    // above the apex, on the other nappe of the equation
+    assert!(cone
+      .cast_ray(&ray(Point3::new(-3., 3., 0.), Vector3::x()))
+      .is_none());
+  }
+}
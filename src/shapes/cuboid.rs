@@ -0,0 +1,176 @@
+use super::Castable;
+use super::{super::ray::Ray, CastInfo};
+use crate::aabb::Aabb;
+use crate::light::{AreaLight, AreaShape, TriangleSet};
+use crate::{material::Material, shapes::Shape};
+use na::{Isometry3, Point2, Point3, Unit, Vector3};
+use std::sync::Arc;
+
+// box spanning `half_size` on each side of its center along its own axes,
+// which are the world's unless it is rotated
+#[derive(Debug, Clone)]
+pub struct Cuboid {
+  half_size: Vector3<f32>,
+  material: Material,
+
+  world_to_object: Isometry3<f32>,
+  object_to_world: Isometry3<f32>,
+}
+
+impl Cuboid {
+  pub fn new(
+    center: Point3<f32>,
+    half_size: Vector3<f32>,
+    rotation: Vector3<f32>,
+    material: Material,
+  ) -> Cuboid {
+    let model_matrix = Isometry3::new(center.coords, rotation);
+    Cuboid {
+      half_size,
+      material,
+      object_to_world: model_matrix,
+      world_to_object: model_matrix.inverse(),
+    }
+  }
+
+  fn bounds(&self) -> Aabb {
+    Aabb::new(Point3::from(-self.half_size), Point3::from(self.half_size))
+  }
+}
+
+impl Castable for Cuboid {
+  fn cast_ray(&self, world_ray: &Ray) -> Option<CastInfo<'_>> {
+    let ray = world_ray.apply_isometry(self.world_to_object);
+    // leaving it when the ray starts inside
+    let (enter, leave) = self.bounds().clip(&ray, f32::INFINITY)?;
+    let t = if enter > 0. { enter } else { leave };
+    if t <= 0. {
+      return None;
+    }
+    let point_hit = ray.origin + ray.direction.into_inner() * t;
+
+    // the face hit is the one the point is relatively the furthest along
+    let relative = point_hit.coords.component_div(&self.half_size);
+    let axis = relative.iamax();
+    let mut normal = Vector3::zeros();
+    normal[axis] = relative[axis].signum();
+    let mut tangent = Vector3::zeros();
+    // the two other axes across the face, each side going from 0 to 1
+    let (u_axis, v_axis) = match axis {
+      0 => (2, 1),
+      1 => (0, 2),
+      _ => (0, 1),
+    };
+    tangent[u_axis] = 1.;
+    let uv = Point2::new(0.5 + relative[u_axis] / 2., 0.5 + relative[v_axis] / 2.);
+    let normal = Unit::new_unchecked(normal);
+    Some(
+      CastInfo {
+        normal,
+        shading_normal: normal,
+        tangent: Unit::new_unchecked(tangent),
+        pointing_to_viewer: Unit::new_normalize(ray.origin - point_hit),
+        point_hit,
+        object_point: point_hit,
+        distance: t,
+        casted: self,
+        material: &self.material,
+        uv,
+      }
+      .with_bump()
+      .apply_isometry(self.object_to_world),
+    )
+  }
+
+  fn bounding_box(&self) -> Option<Aabb> {
+    Some(self.bounds().transform(&self.object_to_world))
+  }
+
+  // its faces, two triangles each, lighting outwards
+  fn emitters(&self) -> Vec<AreaLight> {
+    if self.material.emission.max_component() <= 0. {
+      return vec![];
+    }
+    let mut triangles = vec![];
+    for axis in 0..3 {
+      // the other two axes, in the order that makes them turn
+      // counter-clockwise around the positive side of `axis`
+      let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+      for side in [-1., 1.] {
+        let corner = |u: f32, v: f32| {
+          let mut point = Point3::origin();
+          point[axis] = side * self.half_size[axis];
+          point[u_axis] = u * self.half_size[u_axis];
+          point[v_axis] = v * side * self.half_size[v_axis];
+          self.object_to_world.transform_point(&point)
+        };
+        let corners = [
+          corner(-1., -1.),
+          corner(1., -1.),
+          corner(1., 1.),
+          corner(-1., 1.),
+        ];
+        triangles.push([corners[0], corners[1], corners[2]]);
+        triangles.push([corners[0], corners[2], corners[3]]);
+      }
+    }
+    TriangleSet::new(triangles)
+      .map(|triangles| {
+        AreaLight::emitting(
+          AreaShape::Triangles(Arc::new(triangles)),
+          self.material.emission,
+        )
+      })
+      .into_iter()
+      .collect()
+  }
+}
+
+impl Shape for Cuboid {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::shapes::test_helpers::{material, ray};
+  use std::f32::consts::FRAC_PI_4;
+
+  #[test]
+  fn test_cast() {
+    let cuboid = Cuboid::new(
+      Point3::new(0., 1., 0.),
+      Vector3::new(1., 0.5, 2.),
+      Vector3::zeros(),
+      material(),
+    );
+
+    // on the top face, facing up, and from inside on the far side
+    let info = cuboid
+      .cast_ray(&ray(Point3::new(0.5, 5., 1.), -Vector3::y()))
+      .unwrap();
+    assert!((info.point_hit - Point3::new(0.5, 1.5, 1.)).norm() < 1e-5);
+    assert!((info.normal.into_inner() - Vector3::y()).norm() < 1e-5);
+    assert!((info.uv - Point2::new(0.75, 0.75)).norm() < 1e-5);
+    let info = cuboid
+      .cast_ray(&ray(Point3::new(0., 1., 0.), Vector3::z()))
+      .unwrap();
+    assert!((info.distance - 2.).abs() < 1e-5);
+    assert!((info.normal.into_inner() - Vector3::z()).norm() < 1e-5);
+    assert!(cuboid
+      .cast_ray(&ray(Point3::new(3., 1., 0.), Vector3::x()))
+      .is_none());
+
+    // turned by 45° around y, its corner is further along x
+    let turned = Cuboid::new(
+      Point3::origin(),
+      Vector3::new(1., 1., 1.),
+      Vector3::y() * FRAC_PI_4,
+      material(),
+    );
+    let info = turned
+      .cast_ray(&ray(Point3::new(-5., 0., 0.), Vector3::x()))
+      .unwrap();
+    assert!((info.point_hit.x + 2f32.sqrt()).abs() < 1e-4);
+    let bounds = turned.bounding_box().unwrap();
+    assert!((bounds.max.x - 2f32.sqrt()).abs() < 1e-4);
+  }
+}
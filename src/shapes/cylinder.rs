@@ -0,0 +1,179 @@
+use super::{super::ray::Ray, CastInfo};
+use super::{find_roots_quadratic, Castable};
+use crate::aabb::Aabb;
+use crate::light::{AreaLight, AreaShape};
+use crate::{material::Material, shapes::Shape};
+use na::{Isometry3, Point2, Point3, Unit, Vector3};
+use std::f32::consts::PI;
+
+// closed by a disk at both ends, around the y axis unless rotated, and
+// `height` high around its center
+#[derive(Debug, Clone)]
+pub struct Cylinder {
+  radius: f32,
+  half_height: f32,
+  material: Material,
+
+  world_to_object: Isometry3<f32>,
+  object_to_world: Isometry3<f32>,
+}
+
+impl Cylinder {
+  pub fn new(
+    center: Point3<f32>,
+    radius: f32,
+    height: f32,
+    rotation: Vector3<f32>,
+    material: Material,
+  ) -> Cylinder {
+    let model_matrix = Isometry3::new(center.coords, rotation);
+    Cylinder {
+      radius,
+      half_height: height / 2.,
+      material,
+      object_to_world: model_matrix,
+      world_to_object: model_matrix.inverse(),
+    }
+  }
+}
+
+impl Castable for Cylinder {
+  fn cast_ray(&self, world_ray: &Ray) -> Option<CastInfo<'_>> {
+    let ray = world_ray.apply_isometry(self.world_to_object);
+    let (o, d) = (ray.origin, ray.direction.into_inner());
+    let (r, h) = (self.radius, self.half_height);
+    // distance to the nearest surface in front of the ray, and whether it
+    // is one of the caps
+    let mut nearest: Option<(f32, bool)> = None;
+    let mut consider = |t: f32, cap: bool| {
+      if t > 0. && nearest.is_none_or(|(nearest_t, _)| t < nearest_t) {
+        nearest = Some((t, cap));
+      }
+    };
+
+    // the side, between the caps
+    let a = d.x * d.x + d.z * d.z;
+    if a > 1e-12 {
+      let b = 2. * (o.x * d.x + o.z * d.z);
+      let c = o.x * o.x + o.z * o.z - r * r;
+      if let Some((t0, t1)) = find_roots_quadratic(a, b, c) {
+        for t in [t0, t1] {
+          if (o.y + t * d.y).abs() <= h {
+            consider(t, false);
+          }
+        }
+      }
+    }
+    for cap in [-h, h] {
+      let t = (cap - o.y) / d.y;
+      let (x, z) = (o.x + t * d.x, o.z + t * d.z);
+      if t.is_finite() && x * x + z * z <= r * r {
+        consider(t, true);
+      }
+    }
+
+    let (t, cap) = nearest?;
+    let point_hit = o + d * t;
+    let p = point_hit.coords;
+    let (normal, tangent, uv) = if cap {
+      (
+        Vector3::new(0., p.y.signum(), 0.),
+        Vector3::x(),
+        Point2::new(0.5 + p.x / (2. * r), 0.5 - p.z / (2. * r)),
+      )
+    } else {
+      // around the axis like the longitude of a sphere, then up
+      (
+        Vector3::new(p.x, 0., p.z) / r,
+        Vector3::new(p.z, 0., -p.x) / r,
+        Point2::new(0.5 + p.x.atan2(p.z) / (2. * PI), 0.5 + p.y / (2. * h)),
+      )
+    };
+    let normal = Unit::new_normalize(normal);
+    Some(
+      CastInfo {
+        normal,
+        shading_normal: normal,
+        tangent: Unit::new_normalize(tangent),
+        pointing_to_viewer: Unit::new_normalize(ray.origin - point_hit),
+        point_hit,
+        object_point: point_hit,
+        distance: t,
+        casted: self,
+        material: &self.material,
+        uv,
+      }
+      .with_bump()
+      .apply_isometry(self.object_to_world),
+    )
+  }
+
+  fn bounding_box(&self) -> Option<Aabb> {
+    let extent = Vector3::new(self.radius, self.half_height, self.radius);
+    Some(Aabb::new(Point3::from(-extent), Point3::from(extent)).transform(&self.object_to_world))
+  }
+
+  fn emitters(&self) -> Vec<AreaLight> {
+    if self.material.emission.max_component() <= 0. {
+      return vec![];
+    }
+    let shape = AreaShape::Cylinder {
+      to_world: self.object_to_world,
+      radius: self.radius,
+      half_height: self.half_height,
+    };
+    vec![AreaLight::emitting(shape, self.material.emission)]
+  }
+}
+
+impl Shape for Cylinder {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::shapes::test_helpers::{material, ray};
+  use std::f32::consts::FRAC_PI_2;
+
+  #[test]
+  fn test_cast() {
+    let cylinder = Cylinder::new(
+      Point3::new(0., 1., 0.),
+      0.5,
+      2.,
+      Vector3::zeros(),
+      material(),
+    );
+
+    // on the side, facing out, and through the top cap
+    let info = cylinder
+      .cast_ray(&ray(Point3::new(-3., 1.5, 0.), Vector3::x()))
+      .unwrap();
+    assert!((info.point_hit - Point3::new(-0.5, 1.5, 0.)).norm() < 1e-5);
+    assert!((info.normal.into_inner() + Vector3::x()).norm() < 1e-5);
+    assert!((info.uv.y - 0.75).abs() < 1e-5);
+    let info = cylinder
+      .cast_ray(&ray(Point3::new(0.2, 5., 0.), -Vector3::y()))
+      .unwrap();
+    assert!((info.point_hit.y - 2.).abs() < 1e-5);
+    assert!((info.normal.into_inner() - Vector3::y()).norm() < 1e-5);
+    // over the top, and from inside
+    assert!(cylinder
+      .cast_ray(&ray(Point3::new(-3., 2.5, 0.), Vector3::x()))
+      .is_none());
+    let info = cylinder
+      .cast_ray(&ray(Point3::new(0., 1., 0.), Vector3::z()))
+      .unwrap();
+    assert!((info.distance - 0.5).abs() < 1e-5);
+
+    // lying along x once turned around z
+    let lying = Cylinder::new(
+      Point3::origin(),
+      0.5,
+      2.,
+      Vector3::z() * FRAC_PI_2,
+      material(),
+    );
+    let bounds = lying.bounding_box().unwrap();
+    assert!((bounds.max - Point3::new(1., 0.5, 0.5)).norm() < 1e-5);
+  }
+}
@@ -0,0 +1,120 @@
+use super::Castable;
+use super::{super::ray::Ray, CastInfo};
+use crate::aabb::Aabb;
+use crate::light::{AreaLight, AreaShape};
+use crate::{material::Material, shapes::Shape};
+use na::{Isometry3, Point2, Point3, Unit, Vector3};
+
+// flat and round, facing up unless rotated, and seen from both sides
+#[derive(Debug, Clone)]
+pub struct Disk {
+  radius: f32,
+  material: Material,
+
+  world_to_object: Isometry3<f32>,
+  object_to_world: Isometry3<f32>,
+}
+
+impl Disk {
+  pub fn new(center: Point3<f32>, radius: f32, rotation: Vector3<f32>, material: Material) -> Disk {
+    let model_matrix = Isometry3::new(center.coords, rotation);
+    Disk {
+      radius,
+      material,
+      object_to_world: model_matrix,
+      world_to_object: model_matrix.inverse(),
+    }
+  }
+}
+
+impl Castable for Disk {
+  fn cast_ray(&self, world_ray: &Ray) -> Option<CastInfo<'_>> {
+    let ray = world_ray.apply_isometry(self.world_to_object);
+    let t = -ray.origin.y / ray.direction.y;
+    if !t.is_finite() || t <= 0. {
+      return None;
+    }
+    let point_hit = ray.origin + ray.direction.into_inner() * t;
+    let (x, z) = (point_hit.x, point_hit.z);
+    if x * x + z * z > self.radius * self.radius {
+      return None;
+    }
+    let normal = Vector3::y_axis();
+    Some(
+      CastInfo {
+        normal,
+        shading_normal: normal,
+        tangent: Vector3::x_axis(),
+        pointing_to_viewer: Unit::new_normalize(ray.origin - point_hit),
+        point_hit,
+        object_point: point_hit,
+        distance: t,
+        casted: self,
+        material: &self.material,
+        uv: Point2::new(0.5 + x / (2. * self.radius), 0.5 - z / (2. * self.radius)),
+      }
+      .with_bump()
+      .apply_isometry(self.object_to_world),
+    )
+  }
+
+  fn bounding_box(&self) -> Option<Aabb> {
+    let extent = Vector3::new(self.radius, 0., self.radius);
+    Some(Aabb::new(Point3::from(-extent), Point3::from(extent)).transform(&self.object_to_world))
+  }
+
+  // lighting the side it faces
+  fn emitters(&self) -> Vec<AreaLight> {
+    if self.material.emission.max_component() <= 0. {
+      return vec![];
+    }
+    let shape = AreaShape::Disk {
+      center: self.object_to_world.transform_point(&Point3::origin()),
+      normal: self.object_to_world * Vector3::y_axis(),
+      radius: self.radius,
+    };
+    vec![AreaLight::emitting(shape, self.material.emission)]
+  }
+}
+
+impl Shape for Disk {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::color::Color;
+  use crate::shapes::test_helpers::{material, ray};
+  use std::f32::consts::FRAC_PI_2;
+
+  #[test]
+  fn test_cast() {
+    // facing -z once turned around x
+    let disk = Disk::new(
+      Point3::new(0., 0., -2.),
+      1.,
+      Vector3::x() * -FRAC_PI_2,
+      material(),
+    );
+    let info = disk
+      .cast_ray(&ray(Point3::new(0.5, 0., 0.), -Vector3::z()))
+      .unwrap();
+    assert!((info.distance - 2.).abs() < 1e-5);
+    assert!((info.normal.into_inner() + Vector3::z()).norm() < 1e-5);
+    assert!((info.uv - Point2::new(0.75, 0.5)).norm() < 1e-5);
+    assert!(disk
+      .cast_ray(&ray(Point3::new(1.5, 0., 0.), -Vector3::z()))
+      .is_none());
+    assert!(disk.emitters().is_empty());
+
+    let lamp = Disk::new(
+      Point3::origin(),
+      1.,
+      Vector3::zeros(),
+      Material {
+        emission: Color::new(1., 1., 1.),
+        ..material()
+      },
+    );
+    assert_eq!(lamp.emitters().len(), 1);
+  }
+}
@@ -0,0 +1,152 @@
+use super::triangle::intersect_triangle;
+use super::Castable;
+use super::{super::ray::Ray, CastInfo};
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::light::{AreaLight, AreaShape, TriangleSet};
+use crate::{material::Material, shapes::Shape};
+use na::{Isometry3, Point2, Point3, Unit, Vector3};
+use std::sync::Arc;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Vertex {
+  pub position: Point3<f32>,
+  // without normals the mesh is shaded flat
+  pub normal: Option<Unit<Vector3<f32>>>,
+  pub uv: Option<Point2<f32>>,
+}
+
+// triangle mesh sharing vertices between faces, each face picking one of
+// the mesh's materials
+#[derive(Debug, Clone)]
+pub struct Mesh {
+  vertices: Vec<Vertex>,
+  triangles: Vec<[usize; 3]>,
+  materials: Vec<Material>,
+  face_materials: Vec<usize>,
+  // over `triangles`, in object space
+  bvh: Bvh,
+  // see `emitters`, built once for the faces of every emissive material
+  emitters: Vec<AreaLight>,
+
+  world_to_object: Isometry3<f32>,
+  object_to_world: Isometry3<f32>,
+}
+
+impl Mesh {
+  pub fn new(
+    vertices: Vec<Vertex>,
+    triangles: Vec<[usize; 3]>,
+    materials: Vec<Material>,
+    face_materials: Vec<usize>,
+    position: Point3<f32>,
+    rotation: Vector3<f32>,
+  ) -> Mesh {
+    let bounds: Vec<Aabb> = triangles
+      .iter()
+      .map(|triangle| Aabb::from_points(triangle.iter().map(|&i| &vertices[i].position)))
+      .collect();
+    let model_matrix = Isometry3::new(position.coords, rotation);
+    // the faces of each emissive material, in world space, lighting the
+    // side of their normal
+    let emitters = materials
+      .iter()
+      .enumerate()
+      .filter(|(_, material)| material.emission.max_component() > 0.)
+      .filter_map(|(index, material)| {
+        let faces = triangles
+          .iter()
+          .zip(&face_materials)
+          .filter(|(_, &face_material)| face_material == index)
+          .map(|(triangle, _)| {
+            triangle.map(|i| model_matrix.transform_point(&vertices[i].position))
+          })
+          .collect();
+        let shape = AreaShape::Triangles(Arc::new(TriangleSet::new(faces)?));
+        Some(AreaLight::emitting(shape, material.emission))
+      })
+      .collect();
+    Mesh {
+      bvh: Bvh::build(&bounds),
+      emitters,
+      vertices,
+      triangles,
+      materials,
+      face_materials,
+      object_to_world: model_matrix,
+      world_to_object: model_matrix.inverse(),
+    }
+  }
+
+  fn cast_to_triangle(&self, ray: &Ray, face: usize) -> Option<CastInfo<'_>> {
+    let [a, b, c] = self.triangles[face];
+    let [v0, v1, v2] = [&self.vertices[a], &self.vertices[b], &self.vertices[c]];
+    let hit = intersect_triangle(ray, [&v0.position, &v1.position, &v2.position])?;
+    let point_hit = ray.origin + hit.t * ray.direction.into_inner();
+
+    let edge1 = v1.position - v0.position;
+    let edge2 = v2.position - v0.position;
+    let normal = Unit::new_normalize(edge1.cross(&edge2));
+    let shading_normal = match (v0.normal, v1.normal, v2.normal) {
+      // smooth shading
+      (Some(n0), Some(n1), Some(n2)) => Unit::new_normalize(
+        n0.into_inner() * (1. - hit.u - hit.v) + n1.into_inner() * hit.u + n2.into_inner() * hit.v,
+      ),
+      _ => normal,
+    };
+    let (uv, tangent) = match (v0.uv, v1.uv, v2.uv) {
+      (Some(uv0), Some(uv1), Some(uv2)) => {
+        let uv =
+          Point2::from(uv0.coords * (1. - hit.u - hit.v) + uv1.coords * hit.u + uv2.coords * hit.v);
+        // the direction in which u grows, solved for along both edges
+        // https://learnopengl.com/Advanced-Lighting/Normal-Mapping
+        let (duv1, duv2) = (uv1 - uv0, uv2 - uv0);
+        let determinant = duv1.x * duv2.y - duv2.x * duv1.y;
+        let tangent = (edge1 * duv2.y - edge2 * duv1.y) * determinant.signum();
+        let tangent = Unit::try_new(tangent, 1e-12).unwrap_or_else(|| Unit::new_normalize(edge1));
+        (uv, tangent)
+      }
+      // barycentric, like a lone triangle
+      _ => (Point2::new(hit.u, hit.v), Unit::new_normalize(edge1)),
+    };
+
+    Some(
+      CastInfo {
+        normal,
+        shading_normal,
+        tangent,
+        pointing_to_viewer: Unit::new_normalize(ray.origin - point_hit),
+        point_hit,
+        object_point: point_hit,
+        distance: hit.t,
+        casted: self,
+        material: &self.materials[self.face_materials[face]],
+        uv,
+      }
+      .with_bump(),
+    )
+  }
+}
+
+impl Castable for Mesh {
+  fn cast_ray(&self, world_ray: &Ray) -> Option<CastInfo<'_>> {
+    let ray = world_ray.apply_isometry(self.world_to_object);
+    self
+      .bvh
+      .cast(&ray, |face| self.cast_to_triangle(&ray, face))
+      .map(|info| info.apply_isometry(self.object_to_world))
+  }
+
+  fn bounding_box(&self) -> Option<Aabb> {
+    Some(
+      Aabb::from_points(self.vertices.iter().map(|vertex| &vertex.position))
+        .transform(&self.object_to_world),
+    )
+  }
+
+  fn emitters(&self) -> Vec<AreaLight> {
+    self.emitters.clone()
+  }
+}
+
+impl Shape for Mesh {}
@@ -1,32 +1,82 @@
+use crate::aabb::Aabb;
+use crate::color::Color;
+use crate::light::AreaLight;
 use crate::material::Material;
 
 use super::ray::Ray;
-use na::{Isometry3, Point3, Unit, Vector3};
+use na::{Isometry3, Point2, Point3, Unit, Vector3};
 use std::fmt::Debug;
+pub mod cone;
+pub mod cuboid;
+pub mod cylinder;
+pub mod disk;
+pub mod mesh;
 pub mod plane;
+pub mod shadows;
 pub mod sphere;
+pub mod torus;
+pub mod triangle;
 
 #[derive(Debug, Copy, Clone)]
 pub struct CastInfo<'a> {
+  // of the geometry, for offsetting rays leaving the surface
   pub normal: Unit<Vector3<f32>>,
+  // for lighting, smoothed or bumped
+  pub shading_normal: Unit<Vector3<f32>>,
+  // along which u grows
+  pub tangent: Unit<Vector3<f32>>,
   pub pointing_to_viewer: Unit<Vector3<f32>>,
   pub point_hit: Point3<f32>,
+  // the point hit in the shape's object space
+  pub object_point: Point3<f32>,
   pub distance: f32,
   pub casted: &'a dyn Shape,
-  pub material: Material,
+  pub material: &'a Material,
+  // texture coordinates of the point hit
+  pub uv: Point2<f32>,
 }
 
 impl<'a> CastInfo<'a> {
   pub fn apply_isometry(&self, isometry: Isometry3<f32>) -> CastInfo<'a> {
     CastInfo {
       normal: Unit::new_unchecked(isometry.transform_vector(&self.normal.into_inner())),
+      shading_normal: Unit::new_unchecked(isometry.transform_vector(&self.shading_normal)),
+      tangent: Unit::new_unchecked(isometry.transform_vector(&self.tangent)),
       point_hit: isometry.transform_point(&self.point_hit),
       pointing_to_viewer: Unit::new_unchecked(isometry.transform_vector(&self.pointing_to_viewer)),
+      object_point: self.object_point,
       distance: self.distance,
       casted: self.casted,
       material: self.material,
+      uv: self.uv,
     }
   }
+
+  // the same hit, shaded with one of the layers of its material
+  pub fn with_material(self, material: &'a Material) -> CastInfo<'a> {
+    CastInfo { material, ..self }
+  }
+
+  // tilts the shading normal by the material's bump; called by shapes in
+  // object space, where textures are looked up
+  pub fn with_bump(self) -> CastInfo<'a> {
+    match &self.material.bump {
+      None => self,
+      Some(bump) => CastInfo {
+        shading_normal: bump.perturb(
+          &self.uv,
+          &self.object_point,
+          &self.shading_normal,
+          &self.tangent,
+        ),
+        ..self
+      },
+    }
+  }
+
+  pub fn surface_color(&self) -> Color {
+    self.material.color.color_at(&self.uv, &self.object_point)
+  }
 }
 
 pub fn get_nearest_cast_info<'a>(
@@ -48,13 +98,67 @@ pub fn get_nearest_cast_info<'a>(
   }
 }
 
+// simplified to either have 0 roots or 2(instead of 0, 1, 2)
+pub fn find_roots_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+  let discriminant = b * b - 4. * a * c;
+  if discriminant < 0. {
+    None
+  } else {
+    let sq = discriminant.sqrt();
+    Some(((-b - sq) / (2. * a), (-b + sq) / (2. * a)))
+  }
+}
+
 pub trait Castable {
   fn cast_ray(&self, ray: &Ray) -> Option<CastInfo>;
-  fn is_shadow_casting(&self) -> bool;
+  // shadows of transparent materials are only partial, so this is about
+  // the shape casting any at all
+  fn is_shadow_casting(&self) -> bool {
+    true
+  }
+  fn receives_shadows(&self) -> bool {
+    true
+  }
+  // world space bounds, None when the shape is unbounded (e.g. an infinite plane)
+  fn bounding_box(&self) -> Option<Aabb>;
+  // the light of the shape's surface where its material is emissive, as
+  // area lights, one per emission; none for shapes that can't be sampled
+  fn emitters(&self) -> Vec<AreaLight> {
+    vec![]
+  }
 }
 
-pub trait Movable {
-  fn move_to(&mut self, direction: Vector3<f32>);
-}
+pub trait Shape: Castable + Debug {}
+
+// what the tests of shapes cast rays at them with
+#[cfg(test)]
+pub mod test_helpers {
+  use super::super::ray::Ray;
+  use crate::color::Color;
+  use crate::material::{Material, MaterialType};
+  use crate::texture::SolidColor;
+  use na::{Point3, Unit, Vector3};
+  use std::sync::Arc;
+
+  // black and matte
+  pub fn material() -> Material {
+    Material {
+      color: Arc::new(SolidColor(Color::zero())),
+      albedo: 1.,
+      material_type: MaterialType::Phong {
+        specular_n: 1,
+        k_diffuse: 1.,
+        k_specular: 0.,
+      },
+      bump: None,
+      emission: Color::zero(),
+    }
+  }
 
-pub trait Shape: Castable + Movable + Debug {}
+  pub fn ray(origin: Point3<f32>, direction: Vector3<f32>) -> Ray {
+    Ray {
+      origin,
+      direction: Unit::new_normalize(direction),
+    }
+  }
+}
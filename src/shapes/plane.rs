@@ -1,13 +1,13 @@
 use super::Castable;
 use super::{super::ray::Ray, CastInfo};
+use crate::aabb::Aabb;
+use crate::light::{AreaLight, AreaShape};
+use crate::material::Material;
+use crate::sampling::orthonormal_basis;
 use crate::shapes::Shape;
-use crate::{
-  material::{Material, MaterialType},
-  shapes::Movable,
-};
-use na::{Isometry3, Point3, Unit, Vector3};
+use na::{Isometry3, Point2, Point3, Unit, Vector3};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct Plane {
   normal: Unit<Vector3<f32>>,
   center: Point3<f32>,
@@ -39,13 +39,6 @@ impl Plane {
 }
 
 impl Castable for Plane {
-  fn is_shadow_casting(&self) -> bool {
-    match self.material.material_type {
-      MaterialType::Refraction { .. } => false,
-      _ => true,
-    }
-  }
-
   // https://www.scratchapixel.com/lessons/3d-basic-rendering/minimal-ray-tracer-rendering-simple-shapes/ray-plane-and-ray-disk-intersection
   fn cast_ray(&self, world_ray: &Ray) -> Option<CastInfo> {
     let ray = world_ray.apply_isometry(self.world_to_object);
@@ -78,23 +71,72 @@ impl Castable for Plane {
       }
       None => {}
     }
+    // in world units along two directions of the plane; x and -z for a
+    // plane facing up
+    let (u_axis, v_axis) = orthonormal_basis(&self.normal);
+    let uv = Point2::new(
+      distance_to_center.xyz().dot(&u_axis),
+      distance_to_center.xyz().dot(&v_axis),
+    );
     Some(
       CastInfo {
         distance: t,
         normal: self.normal,
+        shading_normal: self.normal,
+        tangent: Unit::new_unchecked(u_axis),
         pointing_to_viewer: Unit::new_normalize(ray.origin - point_hit),
         point_hit,
+        object_point: point_hit,
         casted: self,
-        material: self.material,
+        material: &self.material,
+        uv,
       }
+      .with_bump()
       .apply_isometry(self.object_to_world),
     )
   }
-}
 
-impl Movable for Plane {
-  fn move_to(&mut self, _direction: Vector3<f32>) {
-    todo!()
+  fn bounding_box(&self) -> Option<Aabb> {
+    let (x, z) = match self.size {
+      (Some(x), Some(z)) => (x, z),
+      _ => return None,
+    };
+    let n = self.normal.into_inner();
+    // the size bounds x and z, so y can only be found if the plane isn't vertical
+    if n.y.abs() < 1e-6 {
+      return None;
+    }
+    let corners: Vec<Point3<f32>> = [(-x, -z), (-x, z), (x, -z), (x, z)]
+      .iter()
+      .map(|(dx, dz)| self.center + Vector3::new(*dx, -(n.x * dx + n.z * dz) / n.y, *dz))
+      .collect();
+    Some(Aabb::from_points(&corners).transform(&self.object_to_world))
+  }
+
+  // bounded planes only, which are parallelograms
+  fn emitters(&self) -> Vec<AreaLight> {
+    let (x, z) = match self.size {
+      (Some(x), Some(z)) => (x, z),
+      _ => return vec![],
+    };
+    let n = self.normal.into_inner();
+    if self.material.emission.max_component() <= 0. || n.y.abs() < 1e-6 {
+      return vec![];
+    }
+    // half sides along x and z, ordered so that u × v is the normal
+    let along_x = Vector3::new(x, -n.x * x / n.y, 0.);
+    let along_z = Vector3::new(0., -n.z * z / n.y, z);
+    let (u, v) = if along_x.cross(&along_z).dot(&n) > 0. {
+      (along_x, along_z)
+    } else {
+      (along_z, along_x)
+    };
+    let shape = AreaShape::Rectangle {
+      center: self.object_to_world.transform_point(&self.center),
+      u: self.object_to_world.transform_vector(&u),
+      v: self.object_to_world.transform_vector(&v),
+    };
+    vec![AreaLight::emitting(shape, self.material.emission)]
   }
 }
 
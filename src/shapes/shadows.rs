@@ -0,0 +1,43 @@
+use super::{super::ray::Ray, CastInfo};
+use super::{Castable, Shape};
+use crate::aabb::Aabb;
+use crate::light::AreaLight;
+
+// A shape casting or receiving shadows or not, whatever its material: a
+// light fixture that shouldn't darken what it lights, a floor kept free of
+// the shadows of some objects.
+#[derive(Debug)]
+pub struct Shadows {
+  pub shape: Box<dyn Shape + Sync>,
+  pub cast: bool,
+  pub receive: bool,
+}
+
+impl Castable for Shadows {
+  // hits are the wrapper's, for its settings to be found from them
+  fn cast_ray(&self, ray: &Ray) -> Option<CastInfo<'_>> {
+    let info = self.shape.cast_ray(ray)?;
+    Some(CastInfo {
+      casted: self,
+      ..info
+    })
+  }
+
+  fn is_shadow_casting(&self) -> bool {
+    self.cast && self.shape.is_shadow_casting()
+  }
+
+  fn receives_shadows(&self) -> bool {
+    self.receive && self.shape.receives_shadows()
+  }
+
+  fn bounding_box(&self) -> Option<Aabb> {
+    self.shape.bounding_box()
+  }
+
+  fn emitters(&self) -> Vec<AreaLight> {
+    self.shape.emitters()
+  }
+}
+
+impl Shape for Shadows {}
@@ -1,13 +1,14 @@
 use super::{super::ray::Ray, CastInfo};
-use super::{Castable, Movable};
-use crate::{
-  material::{Material, MaterialType},
-  shapes::Shape,
-};
-use na::{Isometry3, Point3, Unit};
+use super::{find_roots_quadratic, Castable};
+use crate::aabb::Aabb;
+use crate::light::{AreaLight, AreaShape};
+use crate::sampling::orthonormal_basis;
+use crate::{material::Material, shapes::Shape};
+use na::{Isometry3, Point2, Point3, Unit};
 use nalgebra::Vector3;
+use std::f32::consts::PI;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct Sphere {
   center: Point3<f32>,
   radius: f32,
@@ -30,17 +31,6 @@ impl Sphere {
   }
 }
 
-// simplified to either have 0 roots or 2(instead of 0, 1, 2)
-fn find_roots_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
-  let discriminant = b * b - 4. * a * c;
-  if discriminant < 0. {
-    None
-  } else {
-    let sq = discriminant.sqrt();
-    Some(((-b - sq) / (2. * a), (-b + sq) / (2. * a)))
-  }
-}
-
 impl Sphere {
   // https://www.scratchapixel.com/lessons/3d-basic-rendering/minimal-ray-tracer-rendering-simple-shapes/ray-sphere-intersection
   fn find_roots_intersection(&self, Ray { origin, direction }: &Ray) -> Option<(f32, f32)> {
@@ -54,13 +44,6 @@ impl Sphere {
 }
 
 impl Castable for Sphere {
-  fn is_shadow_casting(&self) -> bool {
-    match self.material.material_type {
-      MaterialType::Refraction { .. } => false,
-      _ => true,
-    }
-  }
-
   fn cast_ray(&self, world_ray: &Ray) -> Option<CastInfo> {
     let ray = &world_ray.apply_isometry(self.world_to_object);
     let a = self.find_roots_intersection(ray);
@@ -81,26 +64,49 @@ impl Castable for Sphere {
         let point_hit = ray.origin + (t * ray.direction.into_inner());
 
         let normal = Unit::new_normalize(point_hit - self.center);
+        // around the y axis, any direction perpendicular to the normal at the poles
+        let tangent = Unit::try_new(Vector3::new(normal.z, 0., -normal.x), 1e-6)
+          .unwrap_or_else(|| Unit::new_unchecked(orthonormal_basis(&normal).0));
+        // longitude and latitude, u growing to the right seen from +z
+        let uv = Point2::new(
+          0.5 + normal.x.atan2(normal.z) / (2. * PI),
+          0.5 + normal.y.clamp(-1., 1.).asin() / PI,
+        );
 
         Some(
           CastInfo {
             normal: normal,
+            shading_normal: normal,
+            tangent,
             point_hit: point_hit,
+            object_point: point_hit,
             pointing_to_viewer: Unit::new_normalize(ray.origin - point_hit),
             distance: t,
             casted: self,
-            material: self.material,
+            material: &self.material,
+            uv,
           }
+          .with_bump()
           .apply_isometry(self.object_to_world),
         )
       }
     }
   }
-}
 
-impl Movable for Sphere {
-  fn move_to(&mut self, direction: Vector3<f32>) {
-    self.center += direction;
+  fn bounding_box(&self) -> Option<Aabb> {
+    let radius = Vector3::repeat(self.radius);
+    Some(Aabb::new(self.center - radius, self.center + radius).transform(&self.object_to_world))
+  }
+
+  fn emitters(&self) -> Vec<AreaLight> {
+    if self.material.emission.max_component() <= 0. {
+      return vec![];
+    }
+    let shape = AreaShape::Sphere {
+      center: self.object_to_world.transform_point(&self.center),
+      radius: self.radius,
+    };
+    vec![AreaLight::emitting(shape, self.material.emission)]
   }
 }
 
@@ -109,22 +115,11 @@ impl Shape for Sphere {}
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::color::Color;
+  use crate::shapes::test_helpers::material;
 
   #[test]
   fn test_cast() {
-    let sphere = Sphere::new(
-      Point3::new(0., 0., 0.),
-      1.,
-      Material {
-        color: Color::RGB(0, 0, 0),
-        albedo: 1.0,
-        specular_n: 1,
-        k_diffuse: 1.0,
-        k_specular: 0.0,
-        index_of_refraction: None,
-      },
-    );
+    let sphere = Sphere::new(Point3::new(0., 0., 0.), 1., material());
     let front = Unit::new_normalize(Vector3::new(0., 0., 1.));
     // outside cast
     match sphere.cast_ray(&Ray {
@@ -0,0 +1,252 @@
+use super::Castable;
+use super::{super::ray::Ray, CastInfo};
+use crate::aabb::Aabb;
+use crate::light::{AreaLight, AreaShape};
+use crate::{material::Material, shapes::Shape};
+use na::{Isometry3, Point2, Point3, Unit, Vector3};
+use std::f32::consts::PI;
+
+// ring around the y axis unless rotated: a circle of `minor_radius` swept
+// around a circle of `major_radius` centered on its center
+#[derive(Debug, Clone)]
+pub struct Torus {
+  major_radius: f32,
+  minor_radius: f32,
+  material: Material,
+
+  world_to_object: Isometry3<f32>,
+  object_to_world: Isometry3<f32>,
+}
+
+impl Torus {
+  pub fn new(
+    center: Point3<f32>,
+    major_radius: f32,
+    minor_radius: f32,
+    rotation: Vector3<f32>,
+    material: Material,
+  ) -> Torus {
+    let model_matrix = Isometry3::new(center.coords, rotation);
+    Torus {
+      major_radius,
+      minor_radius,
+      material,
+      object_to_world: model_matrix,
+      world_to_object: model_matrix.inverse(),
+    }
+  }
+
+  // distance to the nearest surface in front of the ray, from the quartic
+  // (|p|² + R² - r²)² = 4 R² (x² + z²) for p along the ray
+  fn intersect(&self, ray: &Ray) -> Option<f32> {
+    let major = self.major_radius as f64;
+    let minor = self.minor_radius as f64;
+    let origin = ray.origin.coords.map(f64::from);
+    let d = ray.direction.into_inner().map(f64::from);
+
+    // missing the sphere around it, or starting from where the ray enters
+    // it for the roots to be computed close to the torus
+    let bound = major + minor;
+    let along = -origin.dot(&d);
+    if origin.norm_squared() - along * along > bound * bound {
+      return None;
+    }
+    let start = (along - bound).max(0.);
+    let o = origin + d * start;
+
+    let m = o.norm_squared();
+    let n = o.dot(&d);
+    let k = m + major * major - minor * minor;
+    let four_r2 = 4. * major * major;
+    let coefficients = [
+      4. * n,
+      4. * n * n + 2. * k - four_r2 * (d.x * d.x + d.z * d.z),
+      4. * n * k - 2. * four_r2 * (o.x * d.x + o.z * d.z),
+      k * k - four_r2 * (o.x * o.x + o.z * o.z),
+    ];
+    solve_quartic(coefficients)
+      .iter()
+      .flatten()
+      .map(|t| t + start)
+      // leaving the surface the ray starts from
+      .filter(|&t| t > 1e-4)
+      .min_by(f64::total_cmp)
+      .map(|t| t as f32)
+  }
+}
+
+// the real roots of x⁴ + a x³ + b x² + c x + d, by Ferrari's method
+fn solve_quartic([a, b, c, d]: [f64; 4]) -> [Option<f64>; 4] {
+  // depressed as y⁴ + p y² + q y + r, with x = y - a / 4
+  let shift = -a / 4.;
+  let a2 = a * a;
+  let p = b - 3. * a2 / 8.;
+  let q = c - a * b / 2. + a2 * a / 8.;
+  let r = d - a * c / 4. + a2 * b / 16. - 3. * a2 * a2 / 256.;
+
+  let mut roots = [None; 4];
+  let mut add_quadratic_roots = |offset: usize, b: f64, c: f64| {
+    let discriminant = b * b - 4. * c;
+    if discriminant >= 0. {
+      let root = discriminant.sqrt();
+      roots[offset] = Some((-b - root) / 2.);
+      roots[offset + 1] = Some((-b + root) / 2.);
+    }
+  };
+  if q.abs() < 1e-12 {
+    // biquadratic, solved for y²
+    let mut squares = [None; 4];
+    let discriminant = p * p - 4. * r;
+    if discriminant >= 0. {
+      let root = discriminant.sqrt();
+      squares[0] = Some((-p - root) / 2.);
+      squares[1] = Some((-p + root) / 2.);
+    }
+    for (i, square) in squares.iter().take(2).enumerate() {
+      if let Some(square) = square.filter(|&square| square >= 0.) {
+        roots[2 * i] = Some(-square.sqrt());
+        roots[2 * i + 1] = Some(square.sqrt());
+      }
+    }
+  } else {
+    // with m a positive root of the resolvent cubic, the quartic splits
+    // into two quadratics
+    let m = largest_cubic_root(p, p * p / 4. - r, -q * q / 8.);
+    if m <= 0. {
+      return roots;
+    }
+    let s = (2. * m).sqrt();
+    add_quadratic_roots(0, -s, p / 2. + m + q / (2. * s));
+    add_quadratic_roots(2, s, p / 2. + m - q / (2. * s));
+  }
+
+  // polished by Newton's method on the original quartic
+  let quartic = |x: f64| (((x + a) * x + b) * x + c) * x + d;
+  let derivative = |x: f64| ((4. * x + 3. * a) * x + 2. * b) * x + c;
+  for root in roots.iter_mut().flatten() {
+    *root += shift;
+    for _ in 0..2 {
+      let slope = derivative(*root);
+      if slope.abs() > 1e-12 {
+        *root -= quartic(*root) / slope;
+      }
+    }
+  }
+  roots
+}
+
+// of x³ + a x² + b x + c, by Cardano's method
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+  // depressed as u³ + p u + q, with x = u - a / 3
+  let p = b - a * a / 3.;
+  let q = 2. * a * a * a / 27. - a * b / 3. + c;
+  let discriminant = q * q / 4. + p * p * p / 27.;
+  let u = if discriminant > 0. {
+    let root = discriminant.sqrt();
+    (-q / 2. + root).cbrt() + (-q / 2. - root).cbrt()
+  } else {
+    // three real roots, the largest of which is at the first angle
+    let radius = (-p / 3.).sqrt();
+    let angle = if radius > 0. {
+      (-q / (2. * radius * radius * radius)).clamp(-1., 1.).acos()
+    } else {
+      0.
+    };
+    2. * radius * (angle / 3.).cos()
+  };
+  u - a / 3.
+}
+
+impl Castable for Torus {
+  fn cast_ray(&self, world_ray: &Ray) -> Option<CastInfo<'_>> {
+    let ray = world_ray.apply_isometry(self.world_to_object);
+    let t = self.intersect(&ray)?;
+    let point_hit = ray.origin + ray.direction.into_inner() * t;
+    let p = point_hit.coords;
+
+    // away from the circle at the center of the tube
+    let around = Vector3::new(p.x, 0., p.z);
+    let distance_to_axis = around.norm();
+    let normal = Unit::new_normalize(p - around / distance_to_axis * self.major_radius);
+    // around the ring, then around the tube from its outer equator
+    let uv = Point2::new(
+      0.5 + p.x.atan2(p.z) / (2. * PI),
+      0.5 + p.y.atan2(distance_to_axis - self.major_radius) / (2. * PI),
+    );
+    Some(
+      CastInfo {
+        normal,
+        shading_normal: normal,
+        tangent: Unit::new_normalize(Vector3::new(p.z, 0., -p.x)),
+        pointing_to_viewer: Unit::new_normalize(ray.origin - point_hit),
+        point_hit,
+        object_point: point_hit,
+        distance: t,
+        casted: self,
+        material: &self.material,
+        uv,
+      }
+      .with_bump()
+      .apply_isometry(self.object_to_world),
+    )
+  }
+
+  fn bounding_box(&self) -> Option<Aabb> {
+    let outer = self.major_radius + self.minor_radius;
+    let extent = Vector3::new(outer, self.minor_radius, outer);
+    Some(Aabb::new(Point3::from(-extent), Point3::from(extent)).transform(&self.object_to_world))
+  }
+
+  fn emitters(&self) -> Vec<AreaLight> {
+    if self.material.emission.max_component() <= 0. {
+      return vec![];
+    }
+    let shape = AreaShape::Torus {
+      to_world: self.object_to_world,
+      major_radius: self.major_radius,
+      minor_radius: self.minor_radius,
+    };
+    vec![AreaLight::emitting(shape, self.material.emission)]
+  }
+}
+
+impl Shape for Torus {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::shapes::test_helpers::{material, ray};
+
+  #[test]
+  fn test_cast() {
+    let torus = Torus::new(
+      Point3::new(0., 0., -5.),
+      1.,
+      0.25,
+      Vector3::zeros(),
+      material(),
+    );
+
+    // the outer side of the ring, then its inner side across the hole
+    let info = torus
+      .cast_ray(&ray(Point3::new(-10., 0., -5.), Vector3::x()))
+      .unwrap();
+    assert!((info.point_hit - Point3::new(-1.25, 0., -5.)).norm() < 1e-4);
+    assert!((info.normal.into_inner() + Vector3::x()).norm() < 1e-4);
+    let info = torus
+      .cast_ray(&ray(Point3::new(0., 0., -5.), Vector3::x()))
+      .unwrap();
+    assert!((info.distance - 0.75).abs() < 1e-4);
+    assert!((info.normal.into_inner() + Vector3::x()).norm() < 1e-4);
+    // through the hole, and from above onto the top of the tube
+    assert!(torus
+      .cast_ray(&ray(Point3::new(0., 3., -5.), -Vector3::y()))
+      .is_none());
+    let info = torus
+      .cast_ray(&ray(Point3::new(1., 3., -5.), -Vector3::y()))
+      .unwrap();
+    assert!((info.point_hit.y - 0.25).abs() < 1e-4);
+    assert!((info.normal.into_inner() - Vector3::y()).norm() < 1e-4);
+    assert!((info.uv.y - 0.75).abs() < 1e-4);
+  }
+}
@@ -0,0 +1,166 @@
+use super::Castable;
+use super::{super::ray::Ray, CastInfo};
+use crate::aabb::Aabb;
+use crate::light::{AreaLight, AreaShape, TriangleSet};
+use crate::{material::Material, shapes::Shape};
+use na::{Point2, Point3, Unit, Vector3};
+use std::sync::Arc;
+
+// barycentric coordinates (u, v) weight the second and third vertices
+pub struct TriangleHit {
+  pub t: f32,
+  pub u: f32,
+  pub v: f32,
+}
+
+// Möller–Trumbore, hitting both faces
+// https://www.scratchapixel.com/lessons/3d-basic-rendering/ray-tracing-rendering-a-triangle/moller-trumbore-ray-triangle-intersection
+pub fn intersect_triangle(
+  Ray { origin, direction }: &Ray,
+  [v0, v1, v2]: [&Point3<f32>; 3],
+) -> Option<TriangleHit> {
+  let edge1 = v1 - v0;
+  let edge2 = v2 - v0;
+  let p = direction.cross(&edge2);
+  let determinant = edge1.dot(&p);
+  if determinant.abs() < 1e-8 {
+    // ray parallel to the triangle
+    return None;
+  }
+  let inverse_determinant = 1. / determinant;
+
+  let to_origin = origin - v0;
+  let u = to_origin.dot(&p) * inverse_determinant;
+  if !(0. ..=1.).contains(&u) {
+    return None;
+  }
+  let q = to_origin.cross(&edge1);
+  let v = direction.dot(&q) * inverse_determinant;
+  if v < 0. || u + v > 1. {
+    return None;
+  }
+  let t = edge2.dot(&q) * inverse_determinant;
+  if t < 0. {
+    return None;
+  }
+  Some(TriangleHit { t, u, v })
+}
+
+// a lone triangle, given in world space; normals follow the
+// counter-clockwise winding of the vertices
+#[derive(Debug, Clone)]
+pub struct Triangle {
+  vertices: [Point3<f32>; 3],
+  normal: Unit<Vector3<f32>>,
+  material: Material,
+}
+
+impl Triangle {
+  pub fn new(vertices: [Point3<f32>; 3], material: Material) -> Triangle {
+    let normal =
+      Unit::new_normalize((vertices[1] - vertices[0]).cross(&(vertices[2] - vertices[0])));
+    Triangle {
+      vertices,
+      normal,
+      material,
+    }
+  }
+}
+
+impl Castable for Triangle {
+  fn cast_ray(&self, ray: &Ray) -> Option<CastInfo<'_>> {
+    let [v0, v1, v2] = &self.vertices;
+    let hit = intersect_triangle(ray, [v0, v1, v2])?;
+    let point_hit = ray.origin + hit.t * ray.direction.into_inner();
+    let [v0, v1, _] = &self.vertices;
+    Some(
+      CastInfo {
+        normal: self.normal,
+        shading_normal: self.normal,
+        tangent: Unit::new_normalize(v1 - v0),
+        pointing_to_viewer: Unit::new_normalize(ray.origin - point_hit),
+        point_hit,
+        object_point: point_hit,
+        distance: hit.t,
+        casted: self,
+        material: &self.material,
+        // barycentric, (0, 0) at the first vertex
+        uv: Point2::new(hit.u, hit.v),
+      }
+      .with_bump(),
+    )
+  }
+
+  fn bounding_box(&self) -> Option<Aabb> {
+    Some(Aabb::from_points(&self.vertices))
+  }
+
+  // lighting the side of its normal
+  fn emitters(&self) -> Vec<AreaLight> {
+    if self.material.emission.max_component() <= 0. {
+      return vec![];
+    }
+    TriangleSet::new(vec![self.vertices])
+      .map(|triangles| {
+        AreaLight::emitting(
+          AreaShape::Triangles(Arc::new(triangles)),
+          self.material.emission,
+        )
+      })
+      .into_iter()
+      .collect()
+  }
+}
+
+impl Shape for Triangle {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::shapes::test_helpers::material;
+
+  #[test]
+  fn test_cast() {
+    let triangle = Triangle::new(
+      [
+        Point3::new(-1., 0., 0.),
+        Point3::new(1., 0., 0.),
+        Point3::new(0., 1., 0.),
+      ],
+      material(),
+    );
+    let front = Unit::new_normalize(Vector3::new(0., 0., -1.));
+    match triangle.cast_ray(&Ray {
+      origin: Point3::new(0., 0.5, 5.),
+      direction: front,
+    }) {
+      None => panic!("should have intersected"),
+      Some(info) => {
+        assert_eq!(info.point_hit, Point3::new(0., 0.5, 0.));
+        assert_eq!(info.distance, 5.);
+        assert_eq!(info.normal.into_inner(), Vector3::new(0., 0., 1.));
+      }
+    };
+    // from behind
+    assert!(triangle
+      .cast_ray(&Ray {
+        origin: Point3::new(0., 0.5, -5.),
+        direction: -front,
+      })
+      .is_some());
+    // outside of the edges
+    assert!(triangle
+      .cast_ray(&Ray {
+        origin: Point3::new(0.9, 0.9, 5.),
+        direction: front,
+      })
+      .is_none());
+    // pointing away
+    assert!(triangle
+      .cast_ray(&Ray {
+        origin: Point3::new(0., 0.5, 5.),
+        direction: -front,
+      })
+      .is_none());
+  }
+}
@@ -0,0 +1,264 @@
+use crate::color::Color;
+use crate::light::{DirectionalLight, Light, LightSample};
+use crate::ray::Ray;
+use crate::sampling::{uniform_sample_sphere, SamplePattern};
+use na::{Point3, Unit, Vector3};
+use rand::RngCore;
+use std::f32::consts::{FRAC_PI_2, PI};
+
+// Daylight sky of Preetham et al.: the Perez formula fitted to the sky's
+// luminance and chromaticity for a sun position and a turbidity, from 2 for
+// a very clear sky to 10 for a hazy one. The sun itself is a directional
+// light going through the same atmosphere, reddening as it sets, so moving
+// it moves the shadows and the colors of the sky together.
+// https://courses.cs.duke.edu/fall01/cps124/resources/p91-preetham.pdf
+#[derive(Debug, Copy, Clone)]
+pub struct Sky {
+  pub sun: DirectionalLight,
+  // of the sky, the sun's being that of `sun`
+  intensity: f32,
+  samples: u32,
+  // Perez coefficients of Y, x and y, and their values at the zenith
+  coefficients: [[f32; 5]; 3],
+  zenith: [f32; 3],
+}
+
+// kcd/m², as given by the model, to the radiance of the renderer
+const LUMINANCE_SCALE: f32 = 0.05;
+// larger than the real sun, 0.27°, so that it can be seen in small images
+const SUN_ANGULAR_RADIUS: f32 = 0.01;
+// part of the horizon's light the ground sends back
+const GROUND_ALBEDO: f32 = 0.3;
+
+impl Sky {
+  // `to_sun` points towards the sun, `sun_intensity` is its light on a
+  // surface facing it before going through the atmosphere
+  pub fn new(
+    to_sun: Unit<Vector3<f32>>,
+    turbidity: f32,
+    intensity: f32,
+    sun_intensity: f32,
+    samples: u32,
+  ) -> Self {
+    let t = turbidity;
+    let coefficients = [
+      [
+        0.1787 * t - 1.4630,
+        -0.3554 * t + 0.4275,
+        -0.0227 * t + 5.3251,
+        0.1206 * t - 2.5771,
+        -0.0670 * t + 0.3703,
+      ],
+      [
+        -0.0193 * t - 0.2592,
+        -0.0665 * t + 0.0008,
+        -0.0004 * t + 0.2125,
+        -0.0641 * t - 0.8989,
+        -0.0033 * t + 0.0452,
+      ],
+      [
+        -0.0167 * t - 0.2608,
+        -0.0950 * t + 0.0092,
+        -0.0079 * t + 0.2102,
+        -0.0441 * t - 1.6537,
+        -0.0109 * t + 0.0529,
+      ],
+    ];
+    // the model breaks down once the sun is below the horizon
+    let theta_sun = to_sun.y.clamp(0.01, 1.).acos();
+    let chi = (4. / 9. - t / 120.) * (PI - 2. * theta_sun);
+    let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+    let polynomial =
+      |c: [f32; 4]| ((c[0] * theta_sun + c[1]) * theta_sun + c[2]) * theta_sun + c[3];
+    let zenith_x = t * t * polynomial([0.00166, -0.00375, 0.00209, 0.])
+      + t * polynomial([-0.02903, 0.06377, -0.03202, 0.00394])
+      + polynomial([0.11693, -0.21196, 0.06052, 0.25886]);
+    let zenith_y = t * t * polynomial([0.00275, -0.00610, 0.00317, 0.])
+      + t * polynomial([-0.04214, 0.08970, -0.04153, 0.00516])
+      + polynomial([0.15346, -0.26756, 0.06670, 0.26688]);
+
+    Sky {
+      sun: DirectionalLight {
+        direction: -to_sun,
+        color: sun_transmittance(to_sun.y, turbidity),
+        intensity: if to_sun.y > 0. { sun_intensity } else { 0. },
+      },
+      intensity,
+      samples,
+      coefficients,
+      zenith: [zenith_luminance.max(0.), zenith_x, zenith_y],
+    }
+  }
+
+  fn towards_sun(&self) -> Unit<Vector3<f32>> {
+    -self.sun.direction
+  }
+
+  // light of the sky alone along `direction`
+  fn sky_color(&self, direction: &Unit<Vector3<f32>>) -> Color {
+    if direction.y < 0. {
+      let horizon = Unit::new_normalize(Vector3::new(direction.x, 0., direction.z));
+      return self.sky_color(&horizon) * GROUND_ALBEDO;
+    }
+    let to_sun = self.towards_sun();
+    let cos_theta = direction.y.max(0.01);
+    let gamma = direction.dot(&to_sun).clamp(-1., 1.).acos();
+    let theta_sun = to_sun.y.clamp(0.01, 1.).acos();
+    let [luminance, x, y] = [0, 1, 2].map(|i| {
+      let perez = |cos_theta: f32, gamma: f32| {
+        let [a, b, c, d, e] = self.coefficients[i];
+        (1. + a * (b / cos_theta).exp()) * (1. + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+      };
+      self.zenith[i] * perez(cos_theta, gamma) / perez(1., theta_sun)
+    });
+    xyy_to_rgb(luminance * LUMINANCE_SCALE * self.intensity, x, y)
+  }
+}
+
+// of the direct light of the sun through `turbidity` of the atmosphere, for
+// red, green and blue wavelengths, when it is `sun_height` (the sine of its
+// elevation) above the horizon: Rayleigh scattering by the air, Ångström's
+// law for the haze
+fn sun_transmittance(sun_height: f32, turbidity: f32) -> Color {
+  let elevation = sun_height.clamp(0., 1.).asin().to_degrees();
+  // relative air mass, Kasten and Young's formula
+  let air_mass = 1. / (sun_height.max(0.) + 0.50572 * (elevation + 6.07995).powf(-1.6364));
+  let beta = 0.04608 * turbidity - 0.04586;
+  let channel = |wavelength: f32| {
+    let rayleigh = 0.008735 * wavelength.powf(-4.08);
+    let haze = beta * wavelength.powf(-1.3);
+    (-air_mass * (rayleigh + haze)).exp()
+  };
+  // in micrometers
+  Color::new(channel(0.65), channel(0.57), channel(0.475))
+}
+
+// CIE xyY to linear sRGB
+fn xyy_to_rgb(luminance: f32, x: f32, y: f32) -> Color {
+  if y <= 0. {
+    return Color::zero();
+  }
+  let big_x = x * luminance / y;
+  let big_z = (1. - x - y) * luminance / y;
+  Color::new(
+    3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+    -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+    0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+  )
+  .map(|c| c.max(0.))
+}
+
+impl Light for Sky {
+  // the sun, then `samples` directions of the sky, uniformly
+  fn samples(&self, point: &Point3<f32>, mut rng: &mut dyn RngCore) -> Vec<LightSample> {
+    let mut samples = if self.sun.intensity > 0. {
+      self.sun.samples(point, rng)
+    } else {
+      vec![]
+    };
+    let count = self.samples.max(1);
+    samples.extend(
+      SamplePattern::Jittered
+        .generate(count, 0, 0, &mut rng)
+        .into_iter()
+        .map(|square_point| {
+          let direction = uniform_sample_sphere(square_point);
+          LightSample {
+            direction,
+            distance: f32::INFINITY,
+            color: self.sky_color(&direction),
+            intensity: 4. * PI / count as f32,
+            in_caustics: false,
+          }
+        }),
+    );
+    samples
+  }
+
+  // with the sun's disk, which only mirrors can see: it is lit through its
+  // sample otherwise
+  fn background(&self, direction: &Unit<Vector3<f32>>) -> Option<Color> {
+    let mut color = self.sky_color(direction);
+    if self.sun.intensity > 0. && direction.dot(&self.towards_sun()) > SUN_ANGULAR_RADIUS.cos() {
+      let solid_angle = PI * SUN_ANGULAR_RADIUS * SUN_ANGULAR_RADIUS;
+      color += self.sun.color * (self.sun.intensity / solid_angle);
+    }
+    Some(color)
+  }
+
+  fn is_seen(&self) -> bool {
+    true
+  }
+
+  fn emits_photons(&self) -> bool {
+    self.sun.intensity > 0.
+  }
+
+  // of the sun only, the sky being too spread out to focus
+  fn emit_photon(
+    &self,
+    bounds: &(Point3<f32>, f32),
+    rng: &mut dyn RngCore,
+  ) -> Option<(Ray, Color)> {
+    if self.sun.intensity > 0. {
+      self.sun.emit_photon(bounds, rng)
+    } else {
+      None
+    }
+  }
+}
+
+// direction towards the sun from its elevation above the horizon and its
+// azimuth, in radians, turning from -z towards +x
+pub fn sun_direction(elevation: f32, azimuth: f32) -> Unit<Vector3<f32>> {
+  let elevation = elevation.clamp(-FRAC_PI_2, FRAC_PI_2);
+  Unit::new_normalize(Vector3::new(
+    elevation.cos() * azimuth.sin(),
+    elevation.sin(),
+    -elevation.cos() * azimuth.cos(),
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+
+  #[test]
+  fn test_sky() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let sky = |elevation: f32| Sky::new(sun_direction(elevation, 0.5), 3., 1., 2., 8);
+    let noon = sky(1.2);
+    let evening = sky(0.05);
+
+    // brighter around the sun than away from it, bluer overhead
+    let near_sun = sun_direction(1.0, 0.5);
+    let away = sun_direction(1.0, 0.5 + PI);
+    assert!(noon.sky_color(&near_sun).luminance() > noon.sky_color(&away).luminance());
+    let zenith = noon.sky_color(&Vector3::y_axis());
+    assert!(zenith.b > zenith.r);
+
+    // the sun is the first sample, and reddens as it sets
+    let samples = noon.samples(&Point3::origin(), &mut rng);
+    assert_eq!(samples.len(), 9);
+    assert!(
+      (samples[0].direction.into_inner() - sun_direction(1.2, 0.5).into_inner()).norm() < 1e-5
+    );
+    assert_eq!(samples[0].intensity, 2.);
+    let redness = |sky: &Sky| sky.sun.color.r / sky.sun.color.b;
+    assert!(redness(&evening) > redness(&noon));
+    assert!(evening.sun.color.luminance() < noon.sun.color.luminance());
+    assert!(
+      noon
+        .background(&sun_direction(1.2, 0.5))
+        .unwrap()
+        .luminance()
+        > 100. * zenith.luminance()
+    );
+
+    // and gone at night
+    let night = sky(-0.3);
+    assert_eq!(night.samples(&Point3::origin(), &mut rng).len(), 8);
+  }
+}
@@ -0,0 +1,308 @@
+use crate::color::Color;
+use crate::noise::Perlin;
+use na::{Point2, Point3};
+use std::f32::consts::PI;
+use std::fmt::Debug;
+use std::path::Path;
+
+// Color varying over a surface, looked up with the texture coordinates of
+// the point hit, or the point itself in the shape's object space for solid
+// textures, which move with their shape and have no seams.
+pub trait Texture: Debug + Send + Sync {
+  fn color_at(&self, uv: &Point2<f32>, point: &Point3<f32>) -> Color;
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct SolidColor(pub Color);
+
+impl Texture for SolidColor {
+  fn color_at(&self, _uv: &Point2<f32>, _point: &Point3<f32>) -> Color {
+    self.0
+  }
+}
+
+// what happens to texture coordinates outside of [0, 1]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WrapMode {
+  Repeat,
+  // every other repetition flipped, hiding seams
+  Mirror,
+  // the border pixels stretch on
+  Clamp,
+}
+
+impl WrapMode {
+  // pixel index for a possibly out of range one
+  fn wrap(&self, index: i64, size: u32) -> u32 {
+    let size = size as i64;
+    let wrapped = match self {
+      WrapMode::Repeat => index.rem_euclid(size),
+      WrapMode::Mirror => {
+        let period = index.rem_euclid(2 * size);
+        if period < size {
+          period
+        } else {
+          2 * size - 1 - period
+        }
+      }
+      WrapMode::Clamp => index.clamp(0, size - 1),
+    };
+    wrapped as u32
+  }
+}
+
+// pixels of an image file, bilinearly filtered. v goes up, so (0, 0) is the
+// bottom left corner of the image.
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+  width: u32,
+  height: u32,
+  // linear, row by row from the top
+  pixels: Vec<Color>,
+  wrap: WrapMode,
+  // repetitions of the image per unit of texture coordinates
+  scale: (f32, f32),
+}
+
+impl ImageTexture {
+  pub fn new(
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+    wrap: WrapMode,
+    scale: (f32, f32),
+  ) -> Self {
+    ImageTexture {
+      width,
+      height,
+      pixels,
+      wrap,
+      scale,
+    }
+  }
+
+  // any format known to the `image` crate; colors are decoded from sRGB
+  // unless `srgb` is false, for images holding data like normal maps
+  pub fn load(path: &Path, wrap: WrapMode, scale: (f32, f32), srgb: bool) -> Result<Self, String> {
+    let image = image::open(path)
+      .map_err(|e| format!("could not read {}: {}", path.display(), e))?
+      .into_rgb8();
+    let pixels = image
+      .pixels()
+      .map(|pixel| {
+        let [r, g, b] = pixel.0;
+        let (r, g, b) = (r as f32 / 255., g as f32 / 255., b as f32 / 255.);
+        if srgb {
+          Color::from_srgb(r, g, b)
+        } else {
+          Color::new(r, g, b)
+        }
+      })
+      .collect();
+    Ok(ImageTexture::new(
+      image.width(),
+      image.height(),
+      pixels,
+      wrap,
+      scale,
+    ))
+  }
+
+  fn pixel(&self, x: i64, y: i64) -> Color {
+    let x = self.wrap.wrap(x, self.width);
+    let y = self.wrap.wrap(y, self.height);
+    self.pixels[(y * self.width + x) as usize]
+  }
+}
+
+impl Texture for ImageTexture {
+  fn color_at(&self, uv: &Point2<f32>, _point: &Point3<f32>) -> Color {
+    // pixel centers are at half integers
+    let x = uv.x * self.scale.0 * self.width as f32 - 0.5;
+    let y = (1. - uv.y * self.scale.1) * self.height as f32 - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let top = self.pixel(x0, y0) * (1. - tx) + self.pixel(x0 + 1, y0) * tx;
+    let bottom = self.pixel(x0, y0 + 1) * (1. - tx) + self.pixel(x0 + 1, y0 + 1) * tx;
+    top * (1. - ty) + bottom * ty
+  }
+}
+
+// squares of two colors, `frequency` of them per unit of texture coordinates
+#[derive(Debug, Copy, Clone)]
+pub struct Checker {
+  pub even: Color,
+  pub odd: Color,
+  pub frequency: f32,
+}
+
+impl Texture for Checker {
+  fn color_at(&self, uv: &Point2<f32>, _point: &Point3<f32>) -> Color {
+    let cell = (uv.x * self.frequency).floor() + (uv.y * self.frequency).floor();
+    if cell.rem_euclid(2.) < 1. {
+      self.even
+    } else {
+      self.odd
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Axis {
+  U,
+  V,
+}
+
+// linear blend from `start` at 0 to `end` at 1 along one of the texture
+// coordinates, constant past them
+#[derive(Debug, Copy, Clone)]
+pub struct Gradient {
+  pub start: Color,
+  pub end: Color,
+  pub axis: Axis,
+}
+
+impl Texture for Gradient {
+  fn color_at(&self, uv: &Point2<f32>, _point: &Point3<f32>) -> Color {
+    let t = match self.axis {
+      Axis::U => uv.x,
+      Axis::V => uv.y,
+    }
+    .clamp(0., 1.);
+    self.start * (1. - t) + self.end * t
+  }
+}
+
+// solid textures: `scale` is the size of their features, in object space
+// units, and `octaves` the number of levels of details added to them
+
+// blend between two colors following fBm, or turbulence for cloudier looks
+#[derive(Debug, Clone)]
+pub struct Noise {
+  pub perlin: Perlin,
+  pub scale: f32,
+  pub octaves: u32,
+  pub turbulence: bool,
+  pub low: Color,
+  pub high: Color,
+}
+
+impl Texture for Noise {
+  fn color_at(&self, _uv: &Point2<f32>, point: &Point3<f32>) -> Color {
+    let point = point / self.scale;
+    let t = if self.turbulence {
+      self.perlin.turbulence(&point, self.octaves)
+    } else {
+      0.5 * (1. + self.perlin.fbm(&point, self.octaves))
+    };
+    self.low * (1. - t) + self.high * t
+  }
+}
+
+// veins running across x, bent by turbulence
+// https://www.scratchapixel.com/lessons/procedural-generation-virtual-worlds/procedural-patterns-noise-part-1/simple-pattern-examples
+#[derive(Debug, Clone)]
+pub struct Marble {
+  pub perlin: Perlin,
+  pub scale: f32,
+  pub octaves: u32,
+  pub base: Color,
+  pub veins: Color,
+}
+
+const MARBLE_DISTORTION: f32 = 2.;
+
+impl Texture for Marble {
+  fn color_at(&self, _uv: &Point2<f32>, point: &Point3<f32>) -> Color {
+    let point = point / self.scale;
+    let phase = PI * (point.x + MARBLE_DISTORTION * self.perlin.turbulence(&point, self.octaves));
+    // thin dark veins where the sine is near its minimum
+    let t = (0.5 * (1. + phase.sin())).powf(0.3);
+    self.veins * (1. - t) + self.base * t
+  }
+}
+
+// rings around the y axis, wobbling with the noise
+#[derive(Debug, Clone)]
+pub struct Wood {
+  pub perlin: Perlin,
+  pub scale: f32,
+  pub octaves: u32,
+  // per unit of scale
+  pub rings: f32,
+  pub light: Color,
+  pub dark: Color,
+}
+
+const WOOD_DISTORTION: f32 = 0.1;
+// the grain is stretched along the trunk
+const WOOD_GRAIN: f32 = 0.2;
+
+impl Texture for Wood {
+  fn color_at(&self, _uv: &Point2<f32>, point: &Point3<f32>) -> Color {
+    let point = point / self.scale;
+    let grain = Point3::new(point.x, point.y * WOOD_GRAIN, point.z);
+    let radius = (point.x * point.x + point.z * point.z).sqrt()
+      + WOOD_DISTORTION * self.perlin.fbm(&grain, self.octaves);
+    let ring = (radius * self.rings).fract();
+    // sharp transition from early to late wood at the end of each ring
+    let t = ring.powi(3);
+    self.light * (1. - t) + self.dark * t
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_wrap_modes() {
+    let wrapped = |mode: WrapMode| -> Vec<u32> { (-4..7).map(|i| mode.wrap(i, 3)).collect() };
+    assert_eq!(
+      wrapped(WrapMode::Repeat),
+      vec![2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0]
+    );
+    assert_eq!(
+      wrapped(WrapMode::Mirror),
+      vec![2, 2, 1, 0, 0, 1, 2, 2, 1, 0, 0]
+    );
+    assert_eq!(
+      wrapped(WrapMode::Clamp),
+      vec![0, 0, 0, 0, 0, 1, 2, 2, 2, 2, 2]
+    );
+  }
+
+  #[test]
+  fn test_image_texture() {
+    // black on the left, white on the right
+    let (black, white) = (Color::zero(), Color::new(1., 1., 1.));
+    let texture = ImageTexture::new(2, 1, vec![black, white], WrapMode::Clamp, (1., 1.));
+    let origin = Point3::origin();
+    let at = |u: f32| texture.color_at(&Point2::new(u, 0.5), &origin).r;
+    assert_eq!(at(0.25), 0.);
+    assert_eq!(at(0.5), 0.5);
+    assert_eq!(at(0.75), 1.);
+    assert_eq!(at(1.5), 1.);
+    // blending across the seam
+    let repeating = ImageTexture {
+      wrap: WrapMode::Repeat,
+      ..texture
+    };
+    assert_eq!(repeating.color_at(&Point2::new(0., 0.5), &origin).r, 0.5);
+  }
+
+  #[test]
+  fn test_checker() {
+    let checker = Checker {
+      even: Color::zero(),
+      odd: Color::new(1., 1., 1.),
+      frequency: 2.,
+    };
+    let origin = Point3::origin();
+    assert_eq!(checker.color_at(&Point2::new(0.1, 0.1), &origin).r, 0.);
+    assert_eq!(checker.color_at(&Point2::new(0.6, 0.1), &origin).r, 1.);
+    assert_eq!(checker.color_at(&Point2::new(0.6, 0.6), &origin).r, 0.);
+    assert_eq!(checker.color_at(&Point2::new(-0.1, 0.1), &origin).r, 1.);
+  }
+}
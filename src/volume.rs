@@ -0,0 +1,450 @@
+use crate::aabb::Aabb;
+use crate::color::Color;
+use crate::noise::Perlin;
+use crate::ray::Ray;
+use crate::sampling::orthonormal_basis;
+use na::{Point3, Unit, Vector3};
+use rand::Rng;
+use std::f32::consts::PI;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+// How much matter there is at a point of a volume, scaling its absorption
+// and scattering coefficients.
+pub trait Density: Debug + Send + Sync {
+  fn density_at(&self, point: &Point3<f32>) -> f32;
+
+  // no point is denser than this
+  fn max_density(&self) -> f32;
+
+  // the density everywhere, for homogeneous media
+  fn constant(&self) -> Option<f32> {
+    None
+  }
+
+  // outside of which there is none, for densities that have bounds
+  fn bounds(&self) -> Option<Aabb> {
+    None
+  }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Constant(pub f32);
+
+impl Density for Constant {
+  fn density_at(&self, _point: &Point3<f32>) -> f32 {
+    self.0
+  }
+
+  fn max_density(&self) -> f32 {
+    self.0
+  }
+
+  fn constant(&self) -> Option<f32> {
+    Some(self.0)
+  }
+}
+
+// clouds of fBm noise, like the `Noise` texture, between 0 and 1: densities
+// below `threshold` are cut off, leaving gaps between the puffs
+#[derive(Debug, Clone)]
+pub struct NoiseDensity {
+  pub perlin: Perlin,
+  pub scale: f32,
+  pub octaves: u32,
+  pub threshold: f32,
+}
+
+impl Density for NoiseDensity {
+  fn density_at(&self, point: &Point3<f32>) -> f32 {
+    let t = 0.5 * (1. + self.perlin.fbm(&(point / self.scale), self.octaves));
+    ((t - self.threshold) / (1. - self.threshold).max(1e-3)).clamp(0., 1.)
+  }
+
+  fn max_density(&self) -> f32 {
+    1.
+  }
+}
+
+// densities on a regular grid spanning a box, trilinearly interpolated,
+// with none outside of it
+#[derive(Debug, Clone)]
+pub struct Grid {
+  bounds: Aabb,
+  size: (usize, usize, usize),
+  // x varying fastest, then y, then z
+  values: Vec<f32>,
+  max_value: f32,
+}
+
+impl Grid {
+  pub fn new(bounds: Aabb, size: (usize, usize, usize), values: Vec<f32>) -> Result<Grid, String> {
+    if size.0 == 0 || size.1 == 0 || size.2 == 0 {
+      return Err("the grid is empty".to_string());
+    }
+    let expected = size.0 * size.1 * size.2;
+    if values.len() != expected {
+      return Err(format!(
+        "expected {} values for a grid of {:?}, got {}",
+        expected,
+        size,
+        values.len()
+      ));
+    }
+    let max_value = values.iter().cloned().fold(0., f32::max);
+    Ok(Grid {
+      bounds,
+      size,
+      values: values.into_iter().map(|value| value.max(0.)).collect(),
+      max_value,
+    })
+  }
+
+  fn value(&self, x: usize, y: usize, z: usize) -> f32 {
+    self.values[(z * self.size.1 + y) * self.size.0 + x]
+  }
+}
+
+impl Density for Grid {
+  fn density_at(&self, point: &Point3<f32>) -> f32 {
+    let relative = (point - self.bounds.min).component_div(&self.bounds.extent());
+    if relative.iter().any(|&c| !(0. ..=1.).contains(&c)) {
+      return 0.;
+    }
+    // values are at the corners of the cells
+    let cell = |c: f32, size: usize| {
+      let position = c * (size - 1) as f32;
+      let index = (position.floor() as usize).min(size.saturating_sub(2));
+      let next = (index + 1).min(size - 1);
+      (index, next, position - index as f32)
+    };
+    let (x0, x1, tx) = cell(relative.x, self.size.0);
+    let (y0, y1, ty) = cell(relative.y, self.size.1);
+    let (z0, z1, tz) = cell(relative.z, self.size.2);
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let plane = |z: usize| {
+      lerp(
+        lerp(self.value(x0, y0, z), self.value(x1, y0, z), tx),
+        lerp(self.value(x0, y1, z), self.value(x1, y1, z), tx),
+        ty,
+      )
+    };
+    lerp(plane(z0), plane(z1), tz)
+  }
+
+  fn max_density(&self) -> f32 {
+    self.max_value
+  }
+
+  fn bounds(&self) -> Option<Aabb> {
+    Some(self.bounds)
+  }
+}
+
+// where a volume is
+#[derive(Debug, Copy, Clone)]
+pub enum Bounds {
+  // fog filling the whole scene
+  Everywhere,
+  // `Everywhere` once the bounds of the scene's shapes are known: rays
+  // going on forever leave the fog where they leave those
+  Scene(Aabb),
+  Sphere { center: Point3<f32>, radius: f32 },
+  Box(Aabb),
+}
+
+impl Bounds {
+  // fog filling the scene within `scene`, the bounds of its shapes, the
+  // rest being kept as is
+  pub fn within(self, scene: Aabb) -> Bounds {
+    match self {
+      Bounds::Everywhere if scene.extent().iter().all(|c| c.is_finite()) => Bounds::Scene(scene),
+      bounds => bounds,
+    }
+  }
+
+  // distances along the ray where it enters and leaves, within
+  // [0, max_distance]
+  fn clip(&self, ray: &Ray, max_distance: f32) -> Option<(f32, f32)> {
+    let (start, end) = match self {
+      Bounds::Everywhere => (0., max_distance),
+      Bounds::Scene(_) if max_distance.is_finite() => (0., max_distance),
+      // from wherever the ray starts
+      Bounds::Scene(aabb) => aabb.grow(&ray.origin).clip(ray, max_distance)?,
+      Bounds::Sphere { center, radius } => {
+        let to_center = center - ray.origin;
+        let projection = to_center.dot(&ray.direction);
+        let squared_miss = to_center.norm_squared() - projection * projection;
+        if squared_miss > radius * radius {
+          return None;
+        }
+        let half_chord = (radius * radius - squared_miss).sqrt();
+        (
+          (projection - half_chord).max(0.),
+          (projection + half_chord).min(max_distance),
+        )
+      }
+      Bounds::Box(aabb) => aabb.clip(ray, max_distance)?,
+    };
+    if end > start {
+      Some((start, end))
+    } else {
+      None
+    }
+  }
+}
+
+// Participating medium: matter absorbing some of the light going through it
+// and scattering some in other directions, like fog or smoke. Following
+// light through it is a random walk from one interaction to the next.
+// https://pbr-book.org/3ed-2018/Volume_Scattering
+// https://pbr-book.org/3ed-2018/Light_Transport_II_Volume_Rendering/Sampling_Volume_Scattering
+#[derive(Debug, Clone)]
+pub struct Volume {
+  pub bounds: Bounds,
+  // per unit of distance where the density is 1
+  pub absorption: f32,
+  pub scattering: f32,
+  // of the scattered light
+  pub color: Color,
+  // g of the Henyey-Greenstein phase function: from -1 for light scattered
+  // back where it comes from to 1 for light going on, 0 being isotropic
+  pub anisotropy: f32,
+  pub density: Arc<dyn Density>,
+}
+
+// optical depth past which so little light gets through, a thousandth,
+// that the rest of a homogeneous volume isn't worth marching through
+const OPAQUE_DEPTH: f32 = 7.;
+
+impl Volume {
+  fn extinction(&self) -> f32 {
+    self.absorption + self.scattering
+  }
+
+  // chance that light stopped by the medium is scattered rather than
+  // absorbed
+  pub fn albedo(&self) -> f32 {
+    let extinction = self.extinction();
+    if extinction > 0. {
+      self.scattering / extinction
+    } else {
+      0.
+    }
+  }
+
+  // extinction where the medium is the densest, bounding it everywhere
+  fn majorant(&self) -> f32 {
+    self.extinction() * self.density.max_density()
+  }
+
+  // the part of the ray up to `max_distance` going through the volume, if
+  // any light is stopped there
+  fn extent(&self, ray: &Ray, max_distance: f32) -> Option<(f32, f32)> {
+    if self.majorant() <= 0. {
+      return None;
+    }
+    let (start, end) = self.bounds.clip(ray, max_distance)?;
+    match self.density.bounds() {
+      None => Some((start, end)),
+      Some(aabb) => {
+        let (enter, leave) = aabb.clip(ray, end)?;
+        Some((start.max(enter), leave)).filter(|(start, end)| end > start)
+      }
+    }
+  }
+
+  // `extent`, worth marching through: only as far as some light gets in
+  // homogeneous media, whose optical depth is known
+  pub fn segment(&self, ray: &Ray, max_distance: f32) -> Option<(f32, f32)> {
+    let (start, end) = self.extent(ray, max_distance)?;
+    match self.density.constant() {
+      Some(_) => Some((start, end.min(start + OPAQUE_DEPTH / self.majorant()))),
+      None => Some((start, end)),
+    }
+  }
+
+  pub fn density_at(&self, point: &Point3<f32>) -> f32 {
+    self.density.density_at(point)
+  }
+
+  // part of the light getting through the first `max_distance` of the ray;
+  // random, but right on average, for heterogeneous media (ratio tracking)
+  pub fn transmittance(&self, ray: &Ray, max_distance: f32, rng: &mut impl Rng) -> f32 {
+    let (start, end) = match self.extent(ray, max_distance) {
+      None => return 1.,
+      Some(segment) => segment,
+    };
+    if let Some(density) = self.density.constant() {
+      return (-self.extinction() * density * (end - start)).exp();
+    }
+    let majorant = self.majorant();
+    let mut transmittance = 1.;
+    let mut t = start;
+    loop {
+      t -= (1. - rng.gen::<f32>()).ln() / majorant;
+      if t >= end {
+        return transmittance;
+      }
+      let point = ray.origin + ray.direction.into_inner() * t;
+      transmittance *= 1. - self.extinction() * self.density_at(&point) / majorant;
+      if transmittance <= 0. {
+        return 0.;
+      }
+    }
+  }
+
+  // distance to where the ray is stopped by the medium, picked according
+  // to how much light gets that far (delta tracking); None when it gets
+  // through the first `max_distance`
+  pub fn sample_interaction(
+    &self,
+    ray: &Ray,
+    max_distance: f32,
+    rng: &mut impl Rng,
+  ) -> Option<f32> {
+    let (start, end) = self.extent(ray, max_distance)?;
+    let majorant = self.majorant();
+    let mut t = start;
+    loop {
+      t -= (1. - rng.gen::<f32>()).ln() / majorant;
+      if t >= end {
+        return None;
+      }
+      let point = ray.origin + ray.direction.into_inner() * t;
+      if rng.gen::<f32>() * majorant < self.extinction() * self.density_at(&point) {
+        return Some(t);
+      }
+    }
+  }
+
+  // share of the light going along `from` scattered along `to`, per unit
+  // of solid angle
+  pub fn phase(&self, from: &Unit<Vector3<f32>>, to: &Unit<Vector3<f32>>) -> f32 {
+    henyey_greenstein(from.dot(to), self.anisotropy)
+  }
+
+  // new direction of light going along `direction` once scattered, picked
+  // according to the phase function
+  pub fn scatter(&self, direction: &Unit<Vector3<f32>>, rng: &mut impl Rng) -> Unit<Vector3<f32>> {
+    let g = self.anisotropy;
+    let (u, v): (f32, f32) = (rng.gen(), rng.gen());
+    let cos = if g.abs() < 1e-3 {
+      1. - 2. * u
+    } else {
+      let s = (1. - g * g) / (1. - g + 2. * g * u);
+      ((1. + g * g - s * s) / (2. * g)).clamp(-1., 1.)
+    };
+    let sin = (1. - cos * cos).max(0.).sqrt();
+    let angle = 2. * PI * v;
+    let (tangent, bitangent) = orthonormal_basis(direction);
+    Unit::new_normalize(
+      tangent * (sin * angle.cos())
+        + bitangent * (sin * angle.sin())
+        + direction.into_inner() * cos,
+    )
+  }
+}
+
+// for `cos` of the angle between the directions before and after scattering
+fn henyey_greenstein(cos: f32, g: f32) -> f32 {
+  let denominator = 1. + g * g - 2. * g * cos;
+  (1. - g * g) / (4. * PI * denominator * denominator.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+
+  fn medium(bounds: Bounds, density: Arc<dyn Density>) -> Volume {
+    Volume {
+      bounds,
+      absorption: 0.2,
+      scattering: 0.3,
+      color: Color::new(1., 1., 1.),
+      anisotropy: 0.6,
+      density,
+    }
+  }
+
+  #[test]
+  fn test_volume() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let ray = Ray {
+      origin: Point3::new(0., 0., -10.),
+      direction: Vector3::z_axis(),
+    };
+    let sphere = Bounds::Sphere {
+      center: Point3::origin(),
+      radius: 2.,
+    };
+    let homogeneous = medium(sphere, Arc::new(Constant(1.)));
+    assert_eq!(homogeneous.segment(&ray, 100.), Some((8., 12.)));
+    assert_eq!(homogeneous.segment(&ray, 9.), Some((8., 9.)));
+    assert!(homogeneous.segment(&ray, 5.).is_none());
+    let expected = (-0.5f32 * 4.).exp();
+    assert!((homogeneous.transmittance(&ray, 100., &mut rng) - expected).abs() < 1e-6);
+
+    // the same with a grid of that density, tracked stochastically
+    let grid = Grid::new(
+      Aabb::new(Point3::new(-2., -2., -2.), Point3::new(2., 2., 2.)),
+      (2, 2, 2),
+      vec![1.; 8],
+    )
+    .unwrap();
+    assert_eq!(grid.density_at(&Point3::new(0.3, -1., 1.9)), 1.);
+    assert_eq!(grid.density_at(&Point3::new(0., 2.5, 0.)), 0.);
+    let tracked = medium(sphere, Arc::new(grid));
+    let count = 20000;
+    let average = (0..count)
+      .map(|_| tracked.transmittance(&ray, 100., &mut rng))
+      .sum::<f32>()
+      / count as f32;
+    assert!((average - expected).abs() < 0.01, "{}", average);
+    let through = (0..count)
+      .filter(|_| tracked.sample_interaction(&ray, 100., &mut rng).is_none())
+      .count();
+    assert!((through as f32 / count as f32 - expected).abs() < 0.01);
+
+    // a dense grid far along the ray, past where its densest part alone
+    // would have stopped all the light, with nothing before it
+    let far = Grid::new(
+      Aabb::new(Point3::new(-1., -1., 0.), Point3::new(1., 1., 1.)),
+      (2, 2, 2),
+      vec![0., 0., 0., 0., 50., 50., 50., 50.],
+    )
+    .unwrap();
+    let behind = medium(Bounds::Everywhere, Arc::new(far));
+    assert_eq!(behind.segment(&ray, 100.), Some((10., 11.)));
+    assert!(behind.transmittance(&ray, 100., &mut rng) < 1.);
+    assert!(behind.sample_interaction(&ray, 100., &mut rng).is_some());
+
+    // the phase function sums to one, and its samples go forward on average
+    // by g
+    let forward = Vector3::z_axis();
+    let sum: f32 = (0..count)
+      .map(|_| {
+        let direction = crate::sampling::uniform_sample_sphere((rng.gen(), rng.gen()));
+        homogeneous.phase(&forward, &direction) * 4. * PI
+      })
+      .sum();
+    assert!((sum / count as f32 - 1.).abs() < 0.03);
+    let mean_cos = (0..count)
+      .map(|_| homogeneous.scatter(&forward, &mut rng).z)
+      .sum::<f32>()
+      / count as f32;
+    assert!((mean_cos - 0.6).abs() < 0.02, "{}", mean_cos);
+  }
+
+  #[test]
+  fn test_grid_errors() {
+    let bounds = Aabb::new(Point3::origin(), Point3::new(1., 1., 1.));
+    assert!(Grid::new(bounds, (2, 2, 2), vec![0.; 7]).is_err());
+    assert!(Grid::new(bounds, (0, 2, 2), vec![]).is_err());
+    let ramp = Grid::new(bounds, (2, 1, 1), vec![0., 2.]).unwrap();
+    assert_eq!(ramp.density_at(&Point3::new(0.25, 0.5, 0.5)), 0.5);
+    assert_eq!(ramp.max_density(), 2.);
+  }
+}
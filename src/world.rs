@@ -1,76 +1,578 @@
+use crate::aabb::Aabb;
+use crate::brdf::{roughness_to_alpha, sample_ggx_normal, schlick_fresnel, Microfacet};
+use crate::bvh::Bvh;
+use crate::color::Color;
+use crate::light::{Emitter, Light, LightSample};
 use crate::ops::fresnel;
-use crate::{color::Color, light::PointLight};
-use crate::{material::MaterialType, shapes::Shape};
+use crate::photon_map::{Photon, PhotonMap};
+use crate::sampling::cosine_sample_hemisphere;
+use crate::texture::Texture;
+use crate::volume::Volume;
+use crate::{
+  material::{Material, MaterialType},
+  shapes::Shape,
+};
 use crate::{
   ops::{reflect, refract},
   shapes::CastInfo,
 };
 use crate::{ray::Ray, shapes::get_nearest_cast_info};
-use na::Unit;
+use na::{Point3, Unit, Vector3};
+use rand::Rng;
+use rayon::prelude::*;
 use std::f32::consts::PI;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct World<'a> {
   pub shapes: Vec<&'a (dyn Shape + Sync)>,
-  pub lights: Vec<PointLight>,
+  pub lights: Vec<Box<dyn Light>>,
+  pub volumes: Vec<Volume>,
+
+  // acceleration structure over the bounded `shapes`
+  bvh: Bvh,
+  // indices of the shapes without a bounding box, always tested
+  unbounded: Vec<usize>,
+  // see `trace_caustics`
+  caustics: Option<PhotonMap>,
 }
 
-const BACKGROUND: Color = Color::RGB(59, 172, 214);
+// sRGB (59, 172, 214)
+const BACKGROUND: Color = Color::new(0.044, 0.413, 0.672);
+
+// how the light arriving along a camera ray is computed
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Integrator {
+  // direct lighting, perfect mirror reflection and refraction
+  Whitted,
+  // global illumination, noisy until many frames are averaged
+  PathTracing { max_depth: i32 },
+}
+
+impl Integrator {
+  pub fn from_name(name: &str) -> Option<Integrator> {
+    match name {
+      "whitted" => Some(Integrator::Whitted),
+      "path" => Some(Integrator::PathTracing { max_depth: 8 }),
+      _ => None,
+    }
+  }
+}
 const MAX_RAY_DEPTH: i32 = 10;
+// points along a ray at which the light scattered by a volume is gathered
+const MARCH_STEPS: i32 = 32;
+// transparent surfaces a shadow ray goes through before giving up on it
+const MAX_SHADOW_SURFACES: i32 = 16;
+// share of the distance to a point sampled on an emissive shape by which
+// shadow rays may fall short of it
+const EMITTER_TOLERANCE: f32 = 0.01;
+// bounces a path goes through before it may be terminated by russian roulette
+const MIN_PATH_DEPTH: i32 = 3;
+// Phong surfaces reflect all the light they receive, where a white
+// lambertian BRDF reflects 1/π of it. Whitted shading keeps to Phong's
+// convention, scaling the other BRDFs up to it; the path tracer, whose
+// bounces follow the lambertian BRDF, scales Phong down instead, for the
+// light of the lights and that of the bounces to add up.
+const BRDF_SCALE: f32 = PI;
 
 impl<'a> World<'a> {
-  fn get_lighting(&self, info: &CastInfo, specular_n: i32, light: &PointLight) -> (Color, Color) {
-    let nudge = info.normal.into_inner() * 0.001;
+  pub fn new(
+    shapes: Vec<&'a (dyn Shape + Sync)>,
+    mut lights: Vec<Box<dyn Light>>,
+    mut volumes: Vec<Volume>,
+  ) -> World<'a> {
+    // emissive shapes light the rest of the scene too
+    for light in shapes.iter().flat_map(|shape| shape.emitters()) {
+      lights.push(Box::new(Emitter(light)));
+    }
+    let mut bounded = vec![];
+    let mut bounds = vec![];
+    let mut unbounded = vec![];
+    for (index, shape) in shapes.iter().enumerate() {
+      match shape.bounding_box() {
+        Some(aabb) => {
+          bounded.push(index);
+          bounds.push(aabb);
+        }
+        None => unbounded.push(index),
+      }
+    }
+    // fog filling the scene would otherwise stop all the light coming from
+    // infinitely far away
+    let scene = bounds
+      .iter()
+      .fold(Aabb::empty(), |scene, aabb| scene.union(aabb));
+    for volume in &mut volumes {
+      volume.bounds = volume.bounds.within(scene);
+    }
+    // the hierarchy refers to positions in `bounds`, map them back to `shapes`
+    let bvh = Bvh::build(&bounds).map_indices(|i| bounded[i]);
+    World {
+      shapes,
+      lights,
+      volumes,
+      bvh,
+      unbounded,
+      caustics: None,
+    }
+  }
 
-    // https://www.scratchapixel.com/lessons/3d-basic-rendering/introduction-to-shading/shading-normals
-    let distance_to_light = light.ray.origin - info.point_hit;
-    let pointing_to_light = Unit::new_normalize(distance_to_light);
-    let shadow_ray = &Ray {
-      origin: info.point_hit + nudge,
-      direction: pointing_to_light,
+  // Photon mapping of caustics: `photons` rays leave the lights, and those
+  // reaching a diffuse surface through glass or off mirrors are kept, to be
+  // gathered within `radius` of the points shaded. Has to be called again
+  // when shapes or lights change.
+  // https://graphics.stanford.edu/~henrik/papers/ewr7/egwr96.pdf
+  pub fn trace_caustics(&mut self, photons: u32, radius: f32) {
+    let bounds = self
+      .shapes
+      .iter()
+      .filter_map(|shape| shape.bounding_box())
+      .fold(Aabb::empty(), |bounds, aabb| bounds.union(&aabb));
+    let sphere = (bounds.centroid(), bounds.extent().norm() / 2.);
+    let emitting: Vec<&dyn Light> = self
+      .lights
+      .iter()
+      .map(|light| light.as_ref())
+      .filter(|light| light.emits_photons())
+      .collect();
+    if photons == 0 || emitting.is_empty() || !sphere.1.is_finite() {
+      self.caustics = None;
+      return;
+    }
+    // each light sends as many, sharing its power between them
+    let share = emitting.len() as f32 / photons as f32;
+    let world = &*self;
+    let stored: Vec<Photon> = (0..photons as usize)
+      .into_par_iter()
+      .filter_map(|index| {
+        let mut rng = rand::thread_rng();
+        let light = emitting[index % emitting.len()];
+        let (ray, power) = light.emit_photon(&sphere, &mut rng)?;
+        world.trace_photon(ray, power * share, &mut rng)
+      })
+      .collect();
+    self.caustics = Some(PhotonMap::new(stored, radius));
+  }
+
+  // where a photon following `ray` lands on a diffuse surface after going
+  // through glass or off mirrors, if it does
+  fn trace_photon(&self, mut ray: Ray, mut power: Color, rng: &mut impl Rng) -> Option<Photon> {
+    for depth in 0..MAX_RAY_DEPTH {
+      let mut info = self.cast_to_shapes(&ray)?;
+      loop {
+        use MaterialType::*;
+        match info.material.material_type {
+          // lit directly otherwise
+          Phong { .. } | Pbr { .. } if depth == 0 => return None,
+          Phong { .. } | Pbr { .. } => {
+            return Some(Photon {
+              position: info.point_hit,
+              direction: info.pointing_to_viewer,
+              power,
+            })
+          }
+          // the rest of the light is absorbed
+          Reflection {
+            reflectivity,
+            roughness,
+          } => {
+            if rng.gen::<f32>() >= reflectivity {
+              return None;
+            }
+            let microfacet_normal = self.get_microfacet_normal(&info, roughness, rng);
+            ray = self.get_reflected_ray(&info, &microfacet_normal);
+          }
+          Refraction { roughness, .. } => {
+            power = power * transmittance(&info);
+            let microfacet_normal = self.get_microfacet_normal(&info, roughness, rng);
+            let (kr, _, refracted_ray) = self.get_refracted_ray(&info, &microfacet_normal);
+            ray = if rng.gen::<f32>() < kr {
+              self.get_reflected_ray(&info, &microfacet_normal)
+            } else {
+              refracted_ray
+            };
+          }
+          Mix {
+            ref first,
+            ref second,
+            ref mask,
+          } => {
+            let layer = if rng.gen::<f32>() < mask_at(mask, &info) {
+              second
+            } else {
+              first
+            };
+            info = info.with_material(layer);
+            continue;
+          }
+          ClearCoat {
+            ref base,
+            refractive_index,
+            roughness,
+            weight,
+          } => {
+            let coat = clear_coat(refractive_index, roughness);
+            if rng.gen::<f32>() >= coat_fresnel(&coat, &info, weight) {
+              info = info.with_material(base);
+              continue;
+            }
+            let microfacet_normal = self.get_microfacet_normal(&info, roughness, rng);
+            ray = self.get_reflected_ray(&info, &microfacet_normal);
+          }
+        }
+        break;
+      }
+    }
+    None
+  }
+
+  // light focused onto the point hit by glass and mirrors, reflected
+  // towards the viewer by `reflectance` of the direction it comes from
+  fn get_caustics(
+    &self,
+    info: &CastInfo,
+    reflectance: impl Fn(&Unit<Vector3<f32>>) -> Color,
+  ) -> Color {
+    let caustics = match &self.caustics {
+      Some(caustics) => caustics,
+      None => return Color::zero(),
     };
-    let point_to_light_crosses_object = self.cast_to_shadow_casting_shapes(shadow_ray);
-    match point_to_light_crosses_object {
-      None => {}
-      Some(shadow_info) => {
-        // only shadow if casted object is nearer than light
-        if shadow_info.distance < distance_to_light.norm() {
-          return (Color::zero(), Color::zero());
+    let (normal, _) = facing_normals(info);
+    let mut power = Color::zero();
+    caustics.gather(&info.point_hit, |photon| {
+      if photon.direction.dot(&normal) > 0. {
+        power += reflectance(&photon.direction) * photon.power;
+      }
+    });
+    power * (1. / (PI * caustics.radius * caustics.radius))
+  }
+
+  // light arriving at `point` from the parts of `light` that aren't in the
+  // shadow. `normal` is the side of the surface rays leave from, `shadowed`
+  // whether shapes can shadow it. Light in the caustics doesn't go through
+  // glass here, photons bringing it.
+  fn visible_light_samples(
+    &self,
+    point: &Point3<f32>,
+    normal: &Unit<Vector3<f32>>,
+    shadowed: bool,
+    light: &dyn Light,
+    rng: &mut impl Rng,
+  ) -> Vec<LightSample> {
+    let nudge = normal.into_inner() * 0.001;
+
+    // https://www.scratchapixel.com/lessons/3d-basic-rendering/introduction-to-shading/shading-normals
+    let mut samples = light.samples(point, rng);
+    samples.retain_mut(|sample| {
+      let shadow_ray = Ray {
+        origin: point + nudge,
+        direction: sample.direction,
+      };
+      let through_glass = !(sample.in_caustics && self.caustics.is_some());
+      let transmittance =
+        self.light_transmittance(&shadow_ray, sample, shadowed, through_glass, rng);
+      sample.color = sample.color * transmittance;
+      transmittance.max_component() > 0.
+    });
+    samples
+  }
+
+  // part of the light of `sample`, along `ray`, reaching its origin: through
+  // volumes, and when `shadowed`, none behind opaque shapes and, when
+  // `through_glass`, some through transparent ones, tinted by them
+  fn light_transmittance(
+    &self,
+    ray: &Ray,
+    sample: &LightSample,
+    shadowed: bool,
+    through_glass: bool,
+    rng: &mut impl Rng,
+  ) -> Color {
+    let distance = sample.distance;
+    let through_volumes: f32 = self
+      .volumes
+      .iter()
+      .map(|volume| volume.transmittance(ray, distance, rng))
+      .product();
+    let mut transmittance = Color::new(through_volumes, through_volumes, through_volumes);
+    if !shadowed {
+      return transmittance;
+    }
+    if !through_glass {
+      let blocked = self
+        .cast_to_shadow_casting_shapes(ray)
+        .is_some_and(|shadow_info| !reaches_light(&shadow_info, 0., distance));
+      return if blocked {
+        Color::zero()
+      } else {
+        transmittance
+      };
+    }
+    let mut ray = *ray;
+    let mut travelled = 0.;
+    for _ in 0..MAX_SHADOW_SURFACES {
+      let shadow_info = match self.cast_to_shadow_casting_shapes(&ray) {
+        Some(shadow_info) => shadow_info,
+        None => return transmittance,
+      };
+      // only shadow if casted object is nearer than light
+      if reaches_light(&shadow_info, travelled, distance) {
+        return transmittance;
+      }
+      transmittance = transmittance * shadow_transmittance(&shadow_info);
+      if transmittance.max_component() <= 0. {
+        break;
+      }
+      // on through the surface
+      let nudge = ray.direction.into_inner() * 0.001;
+      travelled += shadow_info.distance + 0.001;
+      ray.origin = shadow_info.point_hit + nudge;
+    }
+    Color::zero()
+  }
+
+  // light of all the lights scattered by `volume` at `point` along
+  // `direction`, towards where the ray came from
+  fn get_scattered_lighting(
+    &self,
+    volume: &Volume,
+    point: &Point3<f32>,
+    direction: &Unit<Vector3<f32>>,
+    rng: &mut impl Rng,
+  ) -> Color {
+    let mut color = Color::zero();
+    for light in &self.lights {
+      for sample in light.samples(point, rng) {
+        let shadow_ray = Ray {
+          origin: *point,
+          direction: sample.direction,
+        };
+        // volumes don't gather photons, so light reaches them through glass
+        let transmittance = self.light_transmittance(&shadow_ray, &sample, true, true, rng);
+        // the light goes along the opposite of `sample.direction`, then on
+        // along `direction`
+        let phase = volume.phase(&sample.direction, direction);
+        color += sample.color * transmittance * (sample.intensity * phase);
+      }
+    }
+    color * volume.color
+  }
+
+  // light reaching the origin of `ray` from `behind`, `distance` away,
+  // through the volumes, and scattered towards it by them on the way:
+  // single scattering, gathered at regular steps between the points where
+  // the ray enters or leaves a volume
+  fn through_volumes(&self, ray: &Ray, distance: f32, behind: Color, rng: &mut impl Rng) -> Color {
+    let segments: Vec<(f32, f32, &Volume)> = self
+      .volumes
+      .iter()
+      .filter_map(|volume| {
+        let (start, end) = volume.segment(ray, distance)?;
+        Some((start, end, volume))
+      })
+      .collect();
+    let mut boundaries: Vec<f32> = segments
+      .iter()
+      .flat_map(|&(start, end, _)| vec![start, end])
+      .collect();
+    boundaries.sort_by(|a, b| a.total_cmp(b));
+    boundaries.dedup();
+
+    let mut transmittance = 1.;
+    let mut scattered = Color::zero();
+    for interval in boundaries.windows(2) {
+      let (start, end) = (interval[0], interval[1]);
+      let middle = (start + end) / 2.;
+      let inside: Vec<&Volume> = segments
+        .iter()
+        .filter(|(from, to, _)| *from <= middle && middle <= *to)
+        .map(|&(_, _, volume)| volume)
+        .collect();
+      if inside.is_empty() {
+        continue;
+      }
+      let step = (end - start) / MARCH_STEPS as f32;
+      let offset: f32 = rng.gen();
+      for i in 0..MARCH_STEPS {
+        let point = ray.origin + ray.direction.into_inner() * (start + (i as f32 + offset) * step);
+        let mut extinction = 0.;
+        for volume in &inside {
+          let density = volume.density_at(&point);
+          if density <= 0. {
+            continue;
+          }
+          scattered += self.get_scattered_lighting(volume, &point, &ray.direction, rng)
+            * (transmittance * volume.scattering * density * step);
+          extinction += (volume.absorption + volume.scattering) * density;
         }
+        transmittance *= (-extinction * step).exp();
       }
     }
-    let facing_ratio: f32 = info.normal.dot(&pointing_to_light).max(0.);
-    let reflected_light = reflect(&pointing_to_light.into_inner(), &info.normal);
+    behind * transmittance + scattered
+  }
+
+  // nearest point along `ray` where it is stopped by a volume, before
+  // `distance`, and that volume
+  fn sample_volume_interaction(
+    &self,
+    ray: &Ray,
+    distance: f32,
+    rng: &mut impl Rng,
+  ) -> Option<(f32, &Volume)> {
+    self
+      .volumes
+      .iter()
+      .filter_map(|volume| Some((volume.sample_interaction(ray, distance, rng)?, volume)))
+      .min_by(|a, b| a.0.total_cmp(&b.0))
+  }
+
+  fn get_lighting(
+    &self,
+    info: &CastInfo,
+    specular_n: i32,
+    light: &dyn Light,
+    rng: &mut impl Rng,
+  ) -> (Color, Color) {
+    // lit from the side it is seen from
+    let (normal, shading_normal) = facing_normals(info);
+    self
+      .visible_light_samples(
+        &info.point_hit,
+        &normal,
+        info.casted.receives_shadows(),
+        light,
+        rng,
+      )
+      .iter()
+      .map(|sample| {
+        let facing_ratio: f32 = shading_normal.dot(&sample.direction).max(0.);
+        let reflected_light = reflect(&sample.direction.into_inner(), &shading_normal);
+
+        let diffuse = sample.color
+          * info.surface_color()
+          * (info.material.albedo * sample.intensity * facing_ratio);
+
+        let specular = sample.color
+          * sample.intensity
+          * info
+            .pointing_to_viewer
+            .into_inner()
+            .dot(&reflected_light)
+            .max(0.)
+            .powi(specular_n);
+        (diffuse, specular)
+      })
+      .fold((Color::zero(), Color::zero()), |(a1, a2), (b1, b2)| {
+        (a1 + b1, a2 + b2)
+      })
+  }
+
+  // light of all the lights reflected towards the viewer by a microfacet
+  // surface, whose normals face the viewer, without `BRDF_SCALE`. Only
+  // `seen_specular` of the specular reflection of the lights rays can see is
+  // kept, the rest being found by reflected rays.
+  fn get_microfacet_lighting(
+    &self,
+    info: &CastInfo,
+    brdf: &Microfacet,
+    (normal, shading_normal): (Unit<Vector3<f32>>, Unit<Vector3<f32>>),
+    seen_specular: f32,
+    rng: &mut impl Rng,
+  ) -> Color {
+    let mut color = Color::zero();
+    for light in &self.lights {
+      let specular_share = if light.is_seen() { seen_specular } else { 1. };
+      let samples = self.visible_light_samples(
+        &info.point_hit,
+        &normal,
+        info.casted.receives_shadows(),
+        light.as_ref(),
+        rng,
+      );
+      for sample in samples {
+        let facing_ratio = shading_normal.dot(&sample.direction).max(0.);
+        let (diffuse, specular) =
+          brdf.lobes(&shading_normal, &info.pointing_to_viewer, &sample.direction);
+        color +=
+          (diffuse + specular * specular_share) * sample.color * (sample.intensity * facing_ratio);
+      }
+    }
+    color
+  }
+
+  // light of the lights seen along `ray`, when one of them is nearer than
+  // `distance`
+  fn get_light_emission(&self, ray: &Ray, distance: f32) -> Option<Color> {
+    self
+      .lights
+      .iter()
+      .filter_map(|light| light.cast_ray(ray))
+      .filter(|(light_distance, _)| *light_distance < distance)
+      .min_by(|(a, _), (b, _)| a.total_cmp(b))
+      .map(|(_, color)| color)
+  }
 
-    // intensity at point hit
-    let light_intensity_at_point = light.intensity / (4. * PI * distance_to_light.norm_squared());
-    let diffuse = light.color * (info.material.albedo * light_intensity_at_point * facing_ratio);
+  // light of the lights surrounding the scene seen by a ray leaving it, if
+  // there are any
+  fn get_environment(&self, direction: &Unit<Vector3<f32>>) -> Option<Color> {
+    self
+      .lights
+      .iter()
+      .filter_map(|light| light.background(direction))
+      .reduce(|a, b| a + b)
+  }
 
-    let specular = light.color
-      * light_intensity_at_point
-      * info
-        .pointing_to_viewer
-        .into_inner()
-        .dot(&reflected_light)
-        .max(0.)
-        .powi(specular_n);
-    (diffuse, specular)
+  // normal of the microfacet reflecting or refracting a ray: the shading
+  // normal itself for smooth surfaces, picked in a GGX lobe around it for
+  // rough ones
+  fn get_microfacet_normal(
+    &self,
+    info: &CastInfo,
+    roughness: f32,
+    rng: &mut impl Rng,
+  ) -> Unit<Vector3<f32>> {
+    if roughness <= 0. {
+      return info.shading_normal;
+    }
+    let (_, shading_normal) = facing_normals(info);
+    let h = sample_ggx_normal(&shading_normal, roughness_to_alpha(roughness), rng);
+    // microfacets facing away can't be seen, and would break fresnel
+    if h.dot(&info.pointing_to_viewer) <= 0. {
+      return info.shading_normal;
+    }
+    // back on the side of the shading normal, for refraction
+    if h.dot(&info.shading_normal) < 0. {
+      -h
+    } else {
+      h
+    }
   }
 
-  fn get_reflected_ray(&self, info: &CastInfo) -> Ray {
+  fn get_reflected_ray(&self, info: &CastInfo, microfacet_normal: &Unit<Vector3<f32>>) -> Ray {
     let nudge = info.normal.into_inner() * 0.001;
 
-    let reflection = reflect(&info.pointing_to_viewer, &info.normal);
+    let mut reflection = reflect(&info.pointing_to_viewer, microfacet_normal);
+    // rough surfaces can send rays below themselves
+    let (normal, _) = facing_normals(info);
+    if reflection.dot(&normal) <= 0. {
+      reflection = reflect(&info.pointing_to_viewer, &info.shading_normal);
+    }
     Ray {
       direction: Unit::new_normalize(reflection),
       origin: info.point_hit + nudge,
     }
   }
-  fn get_refracted_ray(&self, info: &CastInfo) -> (f32, f32, Ray) {
+  fn get_refracted_ray(
+    &self,
+    info: &CastInfo,
+    microfacet_normal: &Unit<Vector3<f32>>,
+  ) -> (f32, f32, Ray) {
     let ray_direction = -info.pointing_to_viewer;
     let ior = {
       match info.material.material_type {
-        MaterialType::Refraction { refractive_index } => refractive_index,
+        MaterialType::Refraction {
+          refractive_index, ..
+        } => refractive_index,
         _ => panic!(),
       }
     };
@@ -79,9 +581,9 @@ impl<'a> World<'a> {
 
     let (n_i, n_t, bias, refract_normal) = {
       if outside {
-        (1., ior, -nudge, info.normal)
+        (1., ior, -nudge, *microfacet_normal)
       } else {
-        (ior, 1., nudge, -info.normal)
+        (ior, 1., nudge, -*microfacet_normal)
       }
     };
 
@@ -99,63 +601,354 @@ impl<'a> World<'a> {
         )
       }
       // total internal reflection
-      None => {
-        println!("{} {}", kr, kt);
-        (1., 0., self.get_reflected_ray(&info))
-      }
+      None => (1., 0., self.get_reflected_ray(info, microfacet_normal)),
     }
   }
-  pub fn cast_to_shapes(&self, ray: &Ray) -> Option<CastInfo> {
+  fn cast(&self, ray: &Ray, filter: impl Fn(&dyn Shape) -> bool) -> Option<CastInfo<'a>> {
+    let cast_to = |index: usize| {
+      let shape: &'a (dyn Shape + Sync) = self.shapes[index];
+      if filter(shape) {
+        shape.cast_ray(ray)
+      } else {
+        None
+      }
+    };
+    let nearest_bounded = self.bvh.cast(ray, cast_to);
     self
-      .shapes
+      .unbounded
       .iter()
-      .map(|obj| obj.cast_ray(&ray))
-      .fold(None, get_nearest_cast_info)
+      .map(|&index| cast_to(index))
+      .fold(nearest_bounded, get_nearest_cast_info)
   }
-  pub fn cast_to_shadow_casting_shapes(&self, ray: &Ray) -> Option<CastInfo> {
-    self
-      .shapes
-      .iter()
-      .filter(|obj| obj.is_shadow_casting())
-      .map(|obj| obj.cast_ray(&ray))
-      .fold(None, get_nearest_cast_info)
+  pub fn cast_to_shapes(&self, ray: &Ray) -> Option<CastInfo<'a>> {
+    self.cast(ray, |_| true)
+  }
+  pub fn cast_to_shadow_casting_shapes(&self, ray: &Ray) -> Option<CastInfo<'a>> {
+    self.cast(ray, |shape| shape.is_shadow_casting())
   }
-  pub fn get_color_at_ray(&self, ray: &Ray, depth: i32) -> Color {
+  pub fn get_color(&self, ray: &Ray, integrator: Integrator, rng: &mut impl Rng) -> Color {
+    match integrator {
+      Integrator::Whitted => self.get_color_at_ray(ray, 0, rng),
+      Integrator::PathTracing { max_depth } => self.get_path_traced_color(ray, max_depth, rng),
+    }
+  }
+
+  // `rng` picks one ray in the lobes of rough surfaces, the average over many
+  // samples blurring them
+  pub fn get_color_at_ray(&self, ray: &Ray, depth: i32, rng: &mut impl Rng) -> Color {
     if depth > MAX_RAY_DEPTH {
       return Color::zero();
     }
-    match self.cast_to_shapes(ray) {
-      None => BACKGROUND,
-      Some(info) => {
-        use crate::MaterialType::*;
-        match info.material.material_type {
+    let info = self.cast_to_shapes(ray);
+    let distance = info.as_ref().map_or(f32::INFINITY, |info| info.distance);
+    let color = match self.get_light_emission(ray, distance) {
+      Some(emission) => emission,
+      None => match info {
+        None => self.get_environment(&ray.direction).unwrap_or(BACKGROUND),
+        Some(info) => self.shade(&info, depth, rng),
+      },
+    };
+    if self.volumes.is_empty() {
+      return color;
+    }
+    self.through_volumes(ray, distance, color, rng)
+  }
+
+  // light leaving the point hit towards the viewer
+  fn shade(&self, info: &CastInfo<'a>, depth: i32, rng: &mut impl Rng) -> Color {
+    emitted(info) + self.shade_reflected(info, depth, rng)
+  }
+
+  // of the light arriving at the point hit, what leaves towards the viewer
+  fn shade_reflected(&self, info: &CastInfo<'a>, depth: i32, rng: &mut impl Rng) -> Color {
+    use MaterialType::*;
+    match info.material.material_type {
+      Phong {
+        k_specular,
+        k_diffuse,
+        specular_n,
+      } => {
+        let (diffuse, specular) = self
+          .lights
+          .iter()
+          .map(|light| self.get_lighting(info, specular_n, light.as_ref(), rng))
+          .fold((Color::zero(), Color::zero()), |(a1, a2), (b1, b2)| {
+            (a1 + b1, a2 + b2)
+          });
+        let caustics = self.get_caustics(info, |_| {
+          info.surface_color() * (info.material.albedo * k_diffuse)
+        });
+        diffuse * k_diffuse + specular * k_specular + caustics
+      }
+      Pbr {
+        metallic,
+        roughness,
+        specular,
+      } => {
+        let brdf = microfacet(info, metallic, roughness, specular);
+        let normals = facing_normals(info);
+        // reflections can't be blurred here, so they fade out as the
+        // surface gets rougher instead, the highlights of the lights they
+        // would show taking over
+        let smoothness = (1. - roughness).powi(2);
+        let direct = (self.get_microfacet_lighting(info, &brdf, normals, 1. - smoothness, rng)
+          + self.get_caustics(info, |direction| {
+            brdf.evaluate(&normals.1, &info.pointing_to_viewer, direction)
+          }))
+          * BRDF_SCALE;
+        if smoothness <= 0. {
+          return direct;
+        }
+        let ray = Ray {
+          origin: info.point_hit + normals.0.into_inner() * 0.001,
+          direction: Unit::new_normalize(reflect(&info.pointing_to_viewer, &normals.1)),
+        };
+        let fresnel = schlick_fresnel(brdf.f0(), normals.1.dot(&info.pointing_to_viewer).max(0.));
+        direct + self.get_color_at_ray(&ray, depth + 1, rng) * fresnel * smoothness
+      }
+      Reflection {
+        reflectivity,
+        roughness,
+      } => {
+        let microfacet_normal = self.get_microfacet_normal(info, roughness, rng);
+        let reflected_ray = self.get_reflected_ray(info, &microfacet_normal);
+        (self.get_color_at_ray(&reflected_ray, depth + 1, rng) * reflectivity)
+          + (info.surface_color() * (1. - reflectivity))
+      }
+      Refraction { roughness, .. } => {
+        let microfacet_normal = self.get_microfacet_normal(info, roughness, rng);
+        let reflected_ray = self.get_reflected_ray(info, &microfacet_normal);
+        let reflect_color = self.get_color_at_ray(&reflected_ray, depth + 1, rng);
+        let (kr, kt, refracted_ray) = self.get_refracted_ray(info, &microfacet_normal);
+        let refract_color = self.get_color_at_ray(&refracted_ray, depth + 1, rng);
+        (refract_color * kt + reflect_color * kr) * transmittance(info)
+      }
+      Mix {
+        ref first,
+        ref second,
+        ref mask,
+      } => {
+        let t = mask_at(mask, info);
+        self.shade(&info.with_material(first), depth, rng) * (1. - t)
+          + self.shade(&info.with_material(second), depth, rng) * t
+      }
+      ClearCoat {
+        ref base,
+        refractive_index,
+        roughness,
+        weight,
+      } => {
+        let coat = clear_coat(refractive_index, roughness);
+        let normals = facing_normals(info);
+        let fresnel = coat_fresnel(&coat, info, weight);
+        let microfacet_normal = self.get_microfacet_normal(info, roughness, rng);
+        let reflected_ray = self.get_reflected_ray(info, &microfacet_normal);
+        // the highlights of the lights the reflected ray can't see, with an
+        // f0 of 1 for the coat's share of the light to be `fresnel` only
+        let highlight = Microfacet {
+          specular: 1. / 0.08,
+          ..coat
+        };
+        let reflected = self.get_color_at_ray(&reflected_ray, depth + 1, rng)
+          + self.get_microfacet_lighting(info, &highlight, normals, 0., rng) * BRDF_SCALE;
+        // what the coat doesn't reflect goes through to the base and back
+        self.shade(&info.with_material(base), depth, rng) * (1. - fresnel) + reflected * fresnel
+      }
+    }
+  }
+
+  // Monte Carlo estimate of the light coming back along `ray`, one path per
+  // call: averaging many of them converges to global illumination.
+  // https://www.pbr-book.org/3ed-2018/Light_Transport_I_Surface_Reflection/Path_Tracing
+  pub fn get_path_traced_color(&self, ray: &Ray, max_depth: i32, rng: &mut impl Rng) -> Color {
+    let mut color = Color::zero();
+    // how much of the light found further down the path reaches the camera
+    let mut throughput = Color::new(1., 1., 1.);
+    let mut ray = *ray;
+    // whether the ray was sent by the camera, a mirror or glass: lights are
+    // otherwise accounted for by next event estimation
+    let mut specular_bounce = true;
+    // whether the light of the lights reaching the last surface through
+    // mirrors or glass was gathered from the caustics
+    let mut gathered = false;
+
+    'path: for depth in 0..max_depth {
+      let hit = self.cast_to_shapes(&ray);
+      let distance = hit.as_ref().map_or(f32::INFINITY, |info| info.distance);
+      let interaction = self.sample_volume_interaction(&ray, distance, rng);
+      let distance = interaction.map_or(distance, |(t, _)| t);
+      if let Some(emission) = self.get_light_emission(&ray, distance) {
+        if specular_bounce && !gathered {
+          color += throughput * emission;
+        }
+        break;
+      }
+      if let Some((t, volume)) = interaction {
+        // scattered: lit like a surface would be, then on in a direction
+        // picked by the phase function, which cancels out with its density
+        let point = ray.origin + ray.direction.into_inner() * t;
+        throughput = throughput * volume.albedo();
+        color += throughput * self.get_scattered_lighting(volume, &point, &ray.direction, rng);
+        throughput = throughput * volume.color;
+        specular_bounce = false;
+        gathered = false;
+        ray = Ray {
+          origin: point,
+          direction: volume.scatter(&ray.direction, rng),
+        };
+        continue;
+      }
+      let mut info = match hit {
+        None => {
+          // like emission, lights surrounding the scene are sampled directly
+          match self.get_environment(&ray.direction) {
+            Some(environment) if specular_bounce => color += throughput * environment,
+            Some(_) => (),
+            None => color += throughput * BACKGROUND,
+          }
+          break;
+        }
+        Some(info) => info,
+      };
+
+      // layered materials go through their layers until one is shaded
+      loop {
+        // emissive shapes that are lights were sampled at the last bounce
+        let emission = emitted(&info);
+        let sampled = !specular_bounce || gathered;
+        if emission.max_component() > 0. && (!sampled || info.casted.emitters().is_empty()) {
+          color += throughput * emission;
+        }
+        let material: &'a Material = info.material;
+        use MaterialType::*;
+        match material.material_type {
           Phong {
             k_specular,
             k_diffuse,
             specular_n,
           } => {
-            let (diffuse, specular) = self
+            // next event estimation: point lights can only be reached by
+            // sampling them directly
+            let direct = self
               .lights
               .iter()
-              .map(|light| self.get_lighting(&info, specular_n, light))
-              .fold((Color::zero(), Color::zero()), |(a1, a2), (b1, b2)| {
-                (a1 + b1, a2 + b2)
-              });
-            diffuse * k_diffuse + specular * k_specular
+              .map(|light| {
+                let (diffuse, specular) = self.get_lighting(&info, specular_n, light.as_ref(), rng);
+                diffuse * k_diffuse + specular * k_specular
+              })
+              .fold(Color::zero(), |a, b| a + b);
+            let caustics = self.get_caustics(&info, |_| {
+              info.surface_color() * (info.material.albedo * k_diffuse)
+            });
+            color += throughput * (direct + caustics) * (1. / BRDF_SCALE);
+            gathered = self.caustics.is_some();
+
+            // diffuse bounce: the cosine-weighted sampling cancels out the
+            // cosine term and the 1/PI of the lambertian BRDF
+            let (normal, shading_normal) = facing_normals(&info);
+            throughput = throughput * info.surface_color() * (info.material.albedo * k_diffuse);
+            specular_bounce = false;
+            ray = Ray {
+              origin: info.point_hit + normal.into_inner() * 0.001,
+              direction: cosine_sample_hemisphere(&shading_normal, rng),
+            };
           }
-          Reflection { reflectivity } => {
-            (self.get_color_at_ray(&self.get_reflected_ray(&info), depth + 1) * reflectivity)
-              + (info.material.color * (1. - reflectivity))
+          Pbr {
+            metallic,
+            roughness,
+            specular,
+          } => {
+            let brdf = microfacet(&info, metallic, roughness, specular);
+            let (normal, shading_normal) = facing_normals(&info);
+            let caustics = self.get_caustics(&info, |direction| {
+              brdf.evaluate(&shading_normal, &info.pointing_to_viewer, direction)
+            });
+            let normals = (normal, shading_normal);
+            color += throughput
+              * (self.get_microfacet_lighting(&info, &brdf, normals, 1., rng) + caustics);
+            specular_bounce = false;
+            gathered = self.caustics.is_some();
+
+            match brdf.sample(&shading_normal, &info.pointing_to_viewer, rng) {
+              None => break 'path,
+              Some((direction, weight)) => {
+                throughput = throughput * weight;
+                ray = Ray {
+                  origin: info.point_hit + normal.into_inner() * 0.001,
+                  direction,
+                };
+              }
+            }
+          }
+          Reflection {
+            reflectivity,
+            roughness,
+          } => {
+            color += throughput * info.surface_color() * (1. - reflectivity);
+            throughput = throughput * reflectivity;
+            let microfacet_normal = self.get_microfacet_normal(&info, roughness, rng);
+            ray = self.get_reflected_ray(&info, &microfacet_normal);
+            specular_bounce = true;
+          }
+          Refraction { roughness, .. } => {
+            throughput = throughput * transmittance(&info);
+            specular_bounce = true;
+            // pick one of the two according to fresnel instead of following both
+            let microfacet_normal = self.get_microfacet_normal(&info, roughness, rng);
+            let (kr, _, refracted_ray) = self.get_refracted_ray(&info, &microfacet_normal);
+            ray = if rng.gen::<f32>() < kr {
+              self.get_reflected_ray(&info, &microfacet_normal)
+            } else {
+              refracted_ray
+            };
+          }
+          // one of the layers, with the probability of its weight
+          Mix {
+            ref first,
+            ref second,
+            ref mask,
+          } => {
+            let layer = if rng.gen::<f32>() < mask_at(mask, &info) {
+              second
+            } else {
+              first
+            };
+            info = info.with_material(layer);
+            continue;
           }
-          Refraction { .. } => {
-            let reflect_color = self.get_color_at_ray(&self.get_reflected_ray(&info), depth + 1);
-            let (kr, kt, refracted_ray) = self.get_refracted_ray(&info);
-            let refract_color = self.get_color_at_ray(&refracted_ray, depth + 1);
-            refract_color * kt + reflect_color * kr
+          ClearCoat {
+            ref base,
+            refractive_index,
+            roughness,
+            weight,
+          } => {
+            let coat = clear_coat(refractive_index, roughness);
+            color += throughput
+              * self.get_microfacet_lighting(&info, &coat, facing_normals(&info), 1., rng)
+              * weight;
+            // reflected by the coat or going through to the base
+            if rng.gen::<f32>() >= coat_fresnel(&coat, &info, weight) {
+              info = info.with_material(base);
+              continue;
+            }
+            // the lights it reflects were sampled above
+            let microfacet_normal = self.get_microfacet_normal(&info, roughness, rng);
+            ray = self.get_reflected_ray(&info, &microfacet_normal);
+            specular_bounce = false;
           }
         }
+        break;
+      }
+
+      if depth >= MIN_PATH_DEPTH {
+        let survival = throughput.max_component().min(0.95);
+        if rng.gen::<f32>() >= survival {
+          break;
+        }
+        throughput = throughput * (1. / survival);
       }
     }
+    color
   }
 
   // pub fn trace(&self, ray: &Ray) -> Option<(CastInfo, Option<Ray>)> {
@@ -185,7 +978,7 @@ impl<'a> World<'a> {
   //         break;
   //       }
   //       Some((info, next_ray)) => {
-  //         use crate::MaterialType::*;
+  //         use MaterialType::*;
   //         match info.material.material_type {
   //           Diffuse | Phong { .. } => {
   //             let (diffuse, specular) = self
@@ -223,6 +1016,111 @@ impl<'a> World<'a> {
   // }
 }
 
+// the geometric and shading normals, flipped to the side of the viewer
+fn facing_normals(info: &CastInfo) -> (Unit<Vector3<f32>>, Unit<Vector3<f32>>) {
+  if info.normal.dot(&info.pointing_to_viewer) < 0. {
+    (-info.normal, -info.shading_normal)
+  } else {
+    (info.normal, info.shading_normal)
+  }
+}
+
+// part of the light going through the surface hit: some for glass, which
+// reflects the rest and tints it on the way out, none for opaque materials
+fn shadow_transmittance(info: &CastInfo) -> Color {
+  use MaterialType::*;
+  match info.material.material_type {
+    Refraction {
+      refractive_index, ..
+    } => {
+      let direction = -info.pointing_to_viewer;
+      let (n_i, n_t, normal) = if info.normal.dot(&direction) < 0. {
+        (1., refractive_index, info.normal)
+      } else {
+        (refractive_index, 1., -info.normal)
+      };
+      let (_, kt) = fresnel(&direction, &normal, n_i, n_t);
+      transmittance(info) * kt
+    }
+    Mix {
+      ref first,
+      ref second,
+      ref mask,
+    } => {
+      let t = mask_at(mask, info);
+      shadow_transmittance(&info.with_material(first)) * (1. - t)
+        + shadow_transmittance(&info.with_material(second)) * t
+    }
+    _ => Color::zero(),
+  }
+}
+
+// whether a shadow ray stopped at `info`, after `travelled` along it, got
+// to the light `distance` away: an emissive surface about as far is the one
+// the light was sampled on, missed by a little because of the nudge of the
+// ray
+fn reaches_light(info: &CastInfo, travelled: f32, distance: f32) -> bool {
+  let reached = travelled + info.distance;
+  reached >= distance
+    || (info.material.emission.max_component() > 0.
+      && reached >= distance * (1. - EMITTER_TOLERANCE))
+}
+
+// light given off by the surface hit, from its front only
+fn emitted(info: &CastInfo) -> Color {
+  if info.normal.dot(&info.pointing_to_viewer) > 0. {
+    info.material.emission
+  } else {
+    Color::zero()
+  }
+}
+
+// Beer-Lambert law: the part of the light left after crossing an absorbing
+// medium, for rays leaving it at `info`
+fn transmittance(info: &CastInfo) -> Color {
+  match info.material.material_type {
+    MaterialType::Refraction { absorption, .. }
+      if info.normal.dot(&info.pointing_to_viewer) < 0. =>
+    {
+      absorption.map(|a| (-a * info.distance).exp())
+    }
+    _ => Color::new(1., 1., 1.),
+  }
+}
+
+// how much of a mix `mask` gives to its second material
+fn mask_at(mask: &Arc<dyn Texture>, info: &CastInfo) -> f32 {
+  let color = mask.color_at(&info.uv, &info.object_point);
+  ((color.r + color.g + color.b) / 3.).clamp(0., 1.)
+}
+
+// a varnish only has a specular part
+fn clear_coat(refractive_index: f32, roughness: f32) -> Microfacet {
+  let f0 = ((refractive_index - 1.) / (refractive_index + 1.)).powi(2);
+  Microfacet {
+    base_color: Color::zero(),
+    metallic: 0.,
+    roughness,
+    specular: f0 / 0.08,
+  }
+}
+
+// part of the light reflected by a coat as a whole, seen from the viewer
+fn coat_fresnel(coat: &Microfacet, info: &CastInfo, weight: f32) -> f32 {
+  let (_, shading_normal) = facing_normals(info);
+  let cos = shading_normal.dot(&info.pointing_to_viewer).max(0.);
+  schlick_fresnel(coat.f0(), cos).r * weight
+}
+
+fn microfacet(info: &CastInfo, metallic: f32, roughness: f32, specular: f32) -> Microfacet {
+  Microfacet {
+    base_color: info.surface_color(),
+    metallic,
+    roughness,
+    specular,
+  }
+}
+
 // #[cfg(test)]
 // mod tests {
 //   use super::*;
@@ -271,3 +1169,352 @@ impl<'a> World<'a> {
 //     // };
 //   }
 // }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::light::{DirectionalLight, EnvironmentLight, PointLight};
+  use crate::shapes::mesh::{Mesh, Vertex};
+  use crate::shapes::plane::Plane;
+  use crate::shapes::shadows::Shadows;
+  use crate::shapes::sphere::Sphere;
+  use crate::shapes::test_helpers::{material, ray};
+  use crate::shapes::triangle::Triangle;
+  use crate::shapes::{cone::Cone, cuboid::Cuboid, cylinder::Cylinder, torus::Torus};
+  use crate::texture::SolidColor;
+  use crate::volume::{Bounds, Constant};
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+
+  fn glass(roughness: f32, absorption: Color) -> Material {
+    Material {
+      material_type: MaterialType::Refraction {
+        refractive_index: 1.5,
+        roughness,
+        absorption,
+      },
+      ..material()
+    }
+  }
+
+  fn white() -> Material {
+    Material {
+      color: Arc::new(SolidColor(Color::new(1., 1., 1.))),
+      ..material()
+    }
+  }
+
+  #[test]
+  fn test_back_faces_unlit() {
+    let mut rng = StdRng::seed_from_u64(0);
+    // facing +z, lit from that side
+    let triangle = Triangle::new(
+      [
+        Point3::new(-1., 0., 0.),
+        Point3::new(1., 0., 0.),
+        Point3::new(0., 1., 0.),
+      ],
+      white(),
+    );
+    let light = PointLight {
+      position: Point3::new(0., 0.3, 5.),
+      color: Color::new(1., 1., 1.),
+      intensity: 1000.,
+    };
+    let world = World::new(vec![&triangle], vec![], vec![]);
+
+    let front = world
+      .cast_to_shapes(&ray(Point3::new(0., 0.3, 5.), -Vector3::z()))
+      .unwrap();
+    let (diffuse, _) = world.get_lighting(&front, 1, &light, &mut rng);
+    assert!(diffuse.max_component() > 0.);
+    let back = world
+      .cast_to_shapes(&ray(Point3::new(0., 0.3, -5.), Vector3::z()))
+      .unwrap();
+    let (diffuse, specular) = world.get_lighting(&back, 1, &light, &mut rng);
+    assert_eq!(diffuse.max_component(), 0.);
+    assert_eq!(specular.max_component(), 0.);
+  }
+
+  #[test]
+  fn test_path_traced_plane_under_sky() {
+    let mut rng = StdRng::seed_from_u64(0);
+    // a white lambertian floor reflects all of an even sky's radiance, what
+    // its bounces find being the sky already sampled as a light
+    let floor = Plane::new(
+      Vector3::y_axis(),
+      Point3::origin(),
+      (None, None),
+      Vector3::zeros(),
+      white(),
+    );
+    let sky = EnvironmentLight {
+      color: Color::new(1., 1., 1.),
+      intensity: 0.5,
+      samples: 16,
+    };
+    let world = World::new(vec![&floor], vec![Box::new(sky)], vec![]);
+    let paths = 2000;
+    let radiance = (0..paths)
+      .map(|_| {
+        let down = ray(Point3::new(0., 1., 0.), Vector3::new(0.2, -1., 0.1));
+        world.get_path_traced_color(&down, 5, &mut rng)
+      })
+      .fold(Color::zero(), |a, b| a + b)
+      * (1. / paths as f32);
+    assert!((radiance.r - 0.5).abs() < 0.02, "{:?}", radiance);
+  }
+
+  #[test]
+  fn test_smooth_microfacets() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let sphere = Sphere::new(Point3::origin(), 1., glass(0., Color::zero()));
+    let world = World::new(vec![&sphere], vec![], vec![]);
+    let direction = Unit::new_normalize(Vector3::new(0.3, -0.2, 1.));
+    let outside = ray(Point3::new(-1.2, 1., -5.), direction.into_inner());
+    let inside = ray(Point3::new(0.2, 0.1, 0.), direction.into_inner());
+
+    // the perfect reflection and refraction about the normal, from both sides
+    for (origin, n_i, n_t, flip) in [(outside, 1., 1.5, 1.), (inside, 1.5, 1., -1.)] {
+      let info = world.cast_to_shapes(&origin).unwrap();
+      let microfacet_normal = world.get_microfacet_normal(&info, 0., &mut rng);
+      assert_eq!(microfacet_normal, info.shading_normal);
+      let reflected = world.get_reflected_ray(&info, &microfacet_normal);
+      let expected = reflect(&info.pointing_to_viewer, &info.normal).normalize();
+      assert!((reflected.direction.into_inner() - expected).norm() < 1e-5);
+      let (_, _, refracted) = world.get_refracted_ray(&info, &microfacet_normal);
+      let normal = Unit::new_unchecked(info.normal.into_inner() * flip);
+      let expected = refract(&direction, &normal, n_i, n_t).unwrap().normalize();
+      assert!((refracted.direction.into_inner() - expected).norm() < 1e-5);
+    }
+  }
+
+  #[test]
+  fn test_rough_microfacets_stay_on_their_side() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let sphere = Sphere::new(Point3::origin(), 1., glass(0.8, Color::zero()));
+    let world = World::new(vec![&sphere], vec![], vec![]);
+    let direction = Vector3::new(0.3, -0.2, 1.);
+    let outside = world
+      .cast_to_shapes(&ray(Point3::new(-1.2, 1., -5.), direction))
+      .unwrap();
+    let inside = world
+      .cast_to_shapes(&ray(Point3::new(0.2, 0.1, 0.), direction))
+      .unwrap();
+
+    for _ in 0..1000 {
+      for info in [&outside, &inside] {
+        // +1 outside the sphere, -1 inside
+        let side = info.normal.dot(&info.pointing_to_viewer).signum();
+        let microfacet_normal = world.get_microfacet_normal(info, 0.8, &mut rng);
+        assert!(microfacet_normal.dot(&info.shading_normal) >= 0.);
+        assert!(microfacet_normal.dot(&info.pointing_to_viewer) * side > 0.);
+        // reflections on the viewer's side, refractions through the surface
+        let reflected = world.get_reflected_ray(info, &microfacet_normal);
+        assert!(reflected.direction.dot(&info.normal) * side > 0.);
+        let (_, kt, refracted) = world.get_refracted_ray(info, &microfacet_normal);
+        if kt > 0. {
+          assert!(refracted.direction.dot(&info.normal) * side < 0.);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_absorption() {
+    let absorption = Color::new(0.5, 1., 2.);
+    for radius in [0.5, 1., 2.] {
+      let sphere = Sphere::new(Point3::origin(), radius, glass(0., absorption));
+      let world = World::new(vec![&sphere], vec![], vec![]);
+      // leaving the medium after `radius` in it
+      let info = world
+        .cast_to_shapes(&ray(Point3::origin(), Vector3::x()))
+        .unwrap();
+      let left = transmittance(&info);
+      assert!((left.r - (-0.5 * radius).exp()).abs() < 1e-5);
+      assert!((left.g - (-radius).exp()).abs() < 1e-5);
+      assert!((left.b - (-2. * radius).exp()).abs() < 1e-5);
+      // nothing is absorbed outside of it
+      let info = world
+        .cast_to_shapes(&ray(Point3::new(-5., 0., 0.), Vector3::x()))
+        .unwrap();
+      assert_eq!(transmittance(&info).r, 1.);
+    }
+  }
+
+  #[test]
+  fn test_shadow_transmittance() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let light = PointLight {
+      position: Point3::new(0., 0., 5.),
+      color: Color::new(1., 1., 1.),
+      intensity: 1.,
+    };
+    let point = Point3::new(0., 0., -5.);
+    let sample = &light.samples(&point, &mut rng)[0];
+    let towards_light = ray(point, sample.direction.into_inner());
+    let transmittance = |shape: &(dyn Shape + Sync), rng: &mut StdRng| {
+      let world = World::new(vec![shape], vec![], vec![]);
+      world.light_transmittance(&towards_light, sample, true, true, rng)
+    };
+
+    // straight through a ball of radius 1: 4% reflected on the way in and
+    // out, and absorbed along its diameter, more in red than in the rest
+    let absorption = Color::new(0.5, 0.1, 0.1);
+    let ball = Sphere::new(Point3::origin(), 1., glass(0., absorption));
+    let through_glass = transmittance(&ball, &mut rng);
+    let expected = absorption.map(|a| 0.96 * 0.96 * (-a * 2.).exp());
+    assert!(
+      (through_glass.r - expected.r).abs() < 1e-3,
+      "{:?}",
+      through_glass
+    );
+    assert!(
+      (through_glass.g - expected.g).abs() < 1e-3,
+      "{:?}",
+      through_glass
+    );
+    assert!(through_glass.r < through_glass.g);
+
+    let opaque = Sphere::new(Point3::origin(), 1., white());
+    assert_eq!(transmittance(&opaque, &mut rng).max_component(), 0.);
+
+    let no_shadow = Shadows {
+      shape: Box::new(Sphere::new(Point3::origin(), 1., white())),
+      cast: false,
+      receive: true,
+    };
+    let unshadowed = transmittance(&no_shadow, &mut rng);
+    assert_eq!(unshadowed, Color::new(1., 1., 1.));
+  }
+
+  #[test]
+  fn test_fog_lit_by_the_sun() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let sphere = Sphere::new(Point3::origin(), 1., white());
+    let fog = Volume {
+      bounds: Bounds::Everywhere,
+      absorption: 0.2,
+      scattering: 0.3,
+      color: Color::new(1., 1., 1.),
+      anisotropy: 0.,
+      density: Arc::new(Constant(1.)),
+    };
+    let sun = DirectionalLight {
+      direction: -Vector3::y_axis(),
+      color: Color::new(1., 1., 1.),
+      intensity: 1.,
+    };
+    let world = World::new(vec![&sphere], vec![], vec![fog]);
+
+    // the sun's light goes through the fog from where it enters the scene,
+    // at the top of the sphere, down to the point lit
+    let sample = &sun.samples(&Point3::origin(), &mut rng)[0];
+    let from = Point3::new(3., -1., 0.);
+    let up = ray(from, sample.direction.into_inner());
+    let lit = world.light_transmittance(&up, sample, true, true, &mut rng);
+    assert!((lit.r - (-0.5f32 * 2.).exp()).abs() < 1e-5, "{:?}", lit);
+    // and so does the background, behind a fog as deep as the scene
+    let sky = world.get_color_at_ray(&up, 0, &mut rng);
+    assert!(sky.max_component() > 0.);
+  }
+
+  #[test]
+  fn test_emissive_shapes_are_sampled() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let glow = |r: f32| Material {
+      emission: Color::new(r, r, r),
+      ..material()
+    };
+    let above = Point3::new(0.3, 2.5, -0.2);
+    let tilted = Vector3::new(0.7, 0.2, 0.4);
+    let quad = Mesh::new(
+      [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)]
+        .iter()
+        .map(|&(x, z)| Vertex {
+          position: Point3::new(x, 0., z),
+          normal: None,
+          uv: None,
+        })
+        .collect(),
+      // facing down, in two materials
+      vec![[0, 1, 2], [0, 2, 3]],
+      vec![glow(1.), glow(3.)],
+      vec![0, 1],
+      above,
+      Vector3::new(0.3, 0., 0.2),
+    );
+    let shapes: Vec<Box<dyn Shape + Sync>> = vec![
+      Box::new(Triangle::new(
+        [
+          Point3::new(-1., 2., -1.),
+          Point3::new(1., 2., -1.),
+          Point3::new(0., 2.5, 1.),
+        ],
+        glow(2.),
+      )),
+      Box::new(quad),
+      Box::new(Cuboid::new(
+        above,
+        Vector3::new(1., 0.5, 0.7),
+        tilted,
+        glow(2.),
+      )),
+      Box::new(Cylinder::new(above, 0.6, 1.5, tilted, glow(2.))),
+      Box::new(Cone::new(above, 0.8, 1.2, tilted, glow(2.))),
+      // a ring hiding part of itself
+      Box::new(Torus::new(above, 1., 0.4, tilted, glow(2.))),
+    ];
+
+    // the light of the shape reaching points around it, facing it, from
+    // its samples and as found by rays, π times the emission they see on
+    // average; none from behind flat ones
+    let points = [
+      Point3::origin(),
+      Point3::new(2.5, 3., 0.5),
+      Point3::new(-1., 4.5, -2.),
+    ];
+    for shape in &shapes {
+      let world = World::new(vec![shape.as_ref()], vec![], vec![]);
+      assert!(!world.lights.is_empty(), "{:?}", shape);
+      let mut lit = false;
+      for point in &points {
+        let facing = Unit::new_normalize(above - point);
+        let passes = 400;
+        let sampled = (0..passes)
+          .flat_map(|_| {
+            world
+              .lights
+              .iter()
+              .flat_map(|light| {
+                world.visible_light_samples(point, &facing, true, light.as_ref(), &mut rng)
+              })
+              .collect::<Vec<_>>()
+          })
+          .map(|sample| sample.color.r * sample.intensity * facing.dot(&sample.direction).max(0.))
+          .sum::<f32>()
+          / passes as f32;
+        let rays = 40000;
+        let found = (0..rays)
+          .map(|_| {
+            let direction = cosine_sample_hemisphere(&facing, &mut rng);
+            world
+              .cast_to_shapes(&ray(*point, direction.into_inner()))
+              .map_or(0., |info| emitted(&info).r * PI)
+          })
+          .sum::<f32>()
+          / rays as f32;
+        lit |= found > 0.;
+        assert!(
+          (sampled - found).abs() <= 0.05 * found,
+          "{:?} from {}: {} sampled, {} found",
+          shape,
+          point,
+          sampled,
+          found
+        );
+      }
+      assert!(lit, "{:?}", shape);
+    }
+  }
+}